@@ -51,19 +51,45 @@ fn create_log(
     let new_log = log::Log::new(operator, action, log);
     LOG_STORAGE.with(|log_storage| {
         let mut storage = log_storage.borrow_mut();
-        match storage.get_mut(&user) {
-            None => {
-                storage.insert(user.clone(), BTreeMap::new());
-                storage.get_mut(&user).unwrap().insert(1, vec![new_log]);
-            }
-            Some(data) => {
-                let page_size = data.len() as u64;
-                let log_data = data.get_mut(&page_size).unwrap();
-                if log_data.len() < PAGE_SIZE {
-                    log_data.push(new_log)
-                } else {
-                    let new_page = page_size + 1;
-                    data.insert(new_page, vec![new_log]);
+        push_log(storage.entry(user).or_insert(BTreeMap::new()), new_log);
+    });
+}
+
+fn push_log(data: &mut BTreeMap<u64, Vec<Log>>, new_log: Log) {
+    let page_size = data.len() as u64;
+    match data.get_mut(&page_size) {
+        Some(log_data) if log_data.len() < PAGE_SIZE => log_data.push(new_log),
+        _ => {
+            data.insert(page_size + 1, vec![new_log]);
+        }
+    }
+}
+
+// Logs of a group follow the group when its ownership is transferred to another user
+#[update]
+fn move_logs(from: Principal, group_id: u64, to: Principal) {
+    let caller = ic_cdk::api::caller();
+    unsafe {
+        if caller != MANAGE_CANISTER {
+            return;
+        }
+    }
+
+    let from = User {
+        identity: from,
+        group_id: group_id,
+    };
+    let to = User {
+        identity: to,
+        group_id: group_id,
+    };
+    LOG_STORAGE.with(|log_storage| {
+        let mut storage = log_storage.borrow_mut();
+        if let Some(pages) = storage.remove(&from) {
+            let data = storage.entry(to).or_insert(BTreeMap::new());
+            for (_, logs) in pages {
+                for log in logs {
+                    push_log(data, log);
                 }
             }
         }
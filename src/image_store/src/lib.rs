@@ -178,6 +178,45 @@ async fn group_image_store(user: Principal, group_id: u64, data: Vec<u8>) -> Res
     }
 }
 
+// Images of a group follow the group when its ownership is transferred to another user
+#[update]
+fn move_images(from: Principal, group_id: u64, to: Principal) -> Result<(), String> {
+    let caller = ic_cdk::api::caller();
+    unsafe {
+        if caller != MANAGE_CANISTER {
+            return Err("invalid identity".to_string());
+        }
+    }
+
+    GROUP_IMAGE_STORAGE.with(|image_store| {
+        let mut image_store = image_store.borrow_mut();
+        let image = image_store
+            .get_mut(&from)
+            .and_then(|images| images.remove(&group_id));
+        if let Some(image) = image {
+            image_store
+                .entry(to)
+                .or_insert(HashMap::new())
+                .insert(group_id, image);
+        }
+    });
+    PROJECT_IMAGE_STORAGE.with(|image_store| {
+        let mut image_store = image_store.borrow_mut();
+        let from = Group {
+            user: from,
+            group_id: group_id,
+        };
+        if let Some(images) = image_store.remove(&from) {
+            let to = Group {
+                user: to,
+                group_id: group_id,
+            };
+            image_store.insert(to, images);
+        }
+    });
+    Ok(())
+}
+
 fn write_stable(data: &[u8], size: u64) -> Image {
     unsafe {
         if Ptr + size > Page * PAGE_SIZE {
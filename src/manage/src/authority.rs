@@ -1,12 +1,13 @@
+use crate::permission::Permission;
 use ic_cdk::export::candid::{CandidType, Deserialize};
 use serde::Serialize;
 
-// Project permission management is similar to Linux file operation permission
+// Built-in roles, each one is a fixed set of permissions
 #[derive(CandidType, Debug, Deserialize, Clone, Serialize, Copy)]
 pub enum Authority {
     // You can read the basic information of groups or projects, but you cannot modify them
     Read,
-    // You can read and modify the basic information of the project,
+    // You can read and modify the basic information of the project and top up its canisters,
     // but you can't operate more core functions, such as deleting canisters
     Write,
     // You can do anything
@@ -14,22 +15,32 @@ pub enum Authority {
 }
 
 impl Authority {
-    pub fn authority_check(operator: Authority, opt: Authority) -> bool {
-        let opt = match opt {
-            Self::Read => 1u8,
-            Self::Write => 2u8,
-            Self::Operational => 4u8,
-        };
-
-        let operator = match operator {
-            Self::Read => 1u8,
-            Self::Write => 2u8,
-            Self::Operational => 4u8,
-        };
-
-        if operator >= opt {
-            return true;
+    pub fn permissions(&self) -> Vec<Permission> {
+        match self {
+            Self::Read => vec![Permission::ViewStatus],
+            Self::Write => vec![
+                Permission::ViewStatus,
+                Permission::EditMetadata,
+                Permission::ManageCanisters,
+                Permission::TopUp,
+            ],
+            Self::Operational => vec![
+                Permission::ViewStatus,
+                Permission::EditMetadata,
+                Permission::ManageCanisters,
+                Permission::TopUp,
+                Permission::StartStop,
+                Permission::InstallCode,
+                Permission::Delete,
+                Permission::ManageMembers,
+                Permission::ManageSettings,
+                Permission::ManageProjects,
+                Permission::ManageRoles,
+            ],
         }
-        return false;
+    }
+
+    pub fn allows(&self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
     }
 }
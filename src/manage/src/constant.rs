@@ -1,2 +1,52 @@
 use ic_cdk::export::Principal;
 pub static mut LOG_CANISTER: Principal = Principal::from_slice(&[0]);
+pub static mut IMAGE_STORE_CANISTER: Principal = Principal::from_slice(&[0]);
+// Called with `notify_member_expiry : (ExpiringMember) -> ()`, no notifications are sent when unset
+pub static mut NOTIFICATION_CANISTER: Option<Principal> = None;
+
+// Interval between two rounds of automatic canister top-ups, in nanoseconds
+pub const TOP_UP_INTERVAL: u64 = 60 * 60 * 1_000_000_000;
+// Cycles the manage canister always keeps for itself when topping up from its own balance
+pub const MANAGE_CANISTER_CYCLE_RESERVE: u128 = 1_000_000_000_000;
+// Number of top-up records kept per project
+pub const TOP_UP_RECORD_LIMIT: usize = 100;
+
+// Interval between two rounds of canister status snapshots, in nanoseconds
+pub static mut MONITOR_INTERVAL: u64 = 10 * 60 * 1_000_000_000;
+// Bounds accepted when updating the monitor interval
+pub const MIN_MONITOR_INTERVAL: u64 = 60 * 1_000_000_000;
+pub const MAX_MONITOR_INTERVAL: u64 = 24 * 60 * 60 * 1_000_000_000;
+// Number of status snapshots kept per canister, one week at the default interval
+pub const STATUS_HISTORY_LIMIT: usize = 1008;
+
+// Maximum size of a Wasm module uploaded in chunks
+pub const WASM_MAX_SIZE: usize = 10 * 1024 * 1024;
+// Unfinalized uploads are discarded after one day
+pub const UPLOAD_EXPIRATION: u64 = 24 * 60 * 60 * 1_000_000_000;
+// Finalized uploads are discarded one week after they were begun
+pub const FINALIZED_UPLOAD_EXPIRATION: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+// Number of uploads, finalized or not, kept per project
+pub const UPLOAD_LIMIT: usize = 10;
+
+// Number of module versions kept in the registry of a project
+pub const REGISTRY_VERSION_LIMIT: usize = 50;
+
+// Number of installed modules kept per canister for rollbacks, including the current one
+pub const INSTALL_HISTORY_LIMIT: usize = 3;
+
+// Time between a deletion request and the earliest confirmation, the request can be cancelled meanwhile
+pub const DELETION_COOLDOWN: u64 = 24 * 60 * 60 * 1_000_000_000;
+// Number of status checks made while waiting for a canister to stop
+pub const STOP_POLL_LIMIT: usize = 10;
+
+// Interval between two sweeps removing expired group and project members, in nanoseconds
+pub const MEMBER_SWEEP_INTERVAL: u64 = 60 * 60 * 1_000_000_000;
+// Members are notified once this long before their expiration, in nanoseconds
+pub static mut EXPIRY_NOTICE: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+
+// Bounds accepted when updating canister settings
+pub const MAX_CONTROLLERS: usize = 10;
+pub const MAX_COMPUTE_ALLOCATION: u64 = 100;
+pub const MAX_MEMORY_ALLOCATION: u64 = 1 << 48;
+// One year, in seconds
+pub const MAX_FREEZING_THRESHOLD: u64 = 365 * 24 * 60 * 60;
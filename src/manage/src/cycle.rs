@@ -0,0 +1,184 @@
+use crate::constant;
+use crate::error::ManageError;
+use crate::util;
+use ic_cdk::export::candid::Nat;
+use ic_cdk::export::candid::{CandidType, Deserialize};
+use ic_cdk::export::Principal;
+
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+// Where the cycles of an automatic top-up come from
+#[derive(CandidType, Debug, Deserialize, Clone, PartialEq, Eq)]
+pub enum TopUpSource {
+    // The cycles balance of the manage canister itself, up to the allowance of the project
+    ManageCanister,
+    // The cycles deposited for the project through deposit_project_cycle_budget
+    ProjectBudget,
+}
+
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct TopUpPolicy {
+    pub source: TopUpSource,
+    // Cycles deposited into a canister each time it falls below the project's canister_cycle_floor
+    pub amount: Nat,
+    // Upper limit of cycles deposited into all canisters of the project within one day
+    pub daily_limit: Nat,
+}
+
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct TopUpRecord {
+    pub canister: Principal,
+    pub amount: Nat,
+    pub cycles_before: Nat,
+    pub time: u64,
+}
+
+#[derive(CandidType, Debug, Deserialize, Clone, Default)]
+pub struct ProjectCycle {
+    pub policy: Option<TopUpPolicy>,
+    pub budget: Nat,
+    // Cycles the project may still take from the manage canister balance,
+    // granted by the owner of the manage canister
+    pub manage_allowance: Nat,
+    pub day: u64,
+    pub spent_today: Nat,
+    pub records: Vec<TopUpRecord>,
+}
+
+impl ProjectCycle {
+    pub fn deposit(&mut self, amount: Nat) {
+        self.budget += amount;
+    }
+
+    // Take cycles out of the budget for a payment other than a top-up, such as a canister creation
+    pub fn withdraw(&mut self, amount: &Nat) -> Result<(), ManageError> {
+        if self.budget < *amount {
+            return Err(ManageError::InsufficientCycles(
+                "insufficient project cycle budget".to_string(),
+            ));
+        }
+        self.budget -= amount.clone();
+        Ok(())
+    }
+
+    // Take cycles from the manage canister balance against the allowance of the project,
+    // available is the balance not yet promised to calls in flight
+    pub fn draw_allowance(&mut self, amount: &Nat, available: u128) -> Result<(), ManageError> {
+        if self.manage_allowance < *amount {
+            return Err(ManageError::InsufficientCycles(
+                "insufficient manage canister allowance".to_string(),
+            ));
+        }
+        let required =
+            util::nat_to_u128(amount).saturating_add(constant::MANAGE_CANISTER_CYCLE_RESERVE);
+        if available < required {
+            return Err(ManageError::InsufficientCycles(
+                "insufficient manage canister balance".to_string(),
+            ));
+        }
+        self.manage_allowance -= amount.clone();
+        Ok(())
+    }
+
+    // Give back cycles taken for a payment whose call failed
+    pub fn refund(&mut self, amount: Nat, source: TopUpSource) {
+        match source {
+            TopUpSource::ProjectBudget => self.budget += amount,
+            TopUpSource::ManageCanister => self.manage_allowance += amount,
+        }
+    }
+
+    // Reserve the cycles of one top-up before the deposit call is made,
+    // so that concurrent top-ups of the same project cannot exceed the daily limit or the budget
+    pub fn reserve(&mut self, now: u64, available: u128) -> Result<Nat, ManageError> {
+        let policy = match &self.policy {
+            None => return Err(ManageError::InvalidState("no top-up policy".to_string())),
+            Some(policy) => policy.clone(),
+        };
+        let today = now / DAY_NANOS;
+        if self.day != today {
+            self.day = today;
+            self.spent_today = Nat::default();
+        }
+        if self.spent_today.clone() + policy.amount.clone() > policy.daily_limit {
+            return Err(ManageError::InsufficientCycles(
+                "daily top-up limit reached".to_string(),
+            ));
+        }
+        match policy.source {
+            TopUpSource::ProjectBudget => {
+                if self.budget < policy.amount {
+                    return Err(ManageError::InsufficientCycles(
+                        "insufficient project cycle budget".to_string(),
+                    ));
+                }
+                self.budget -= policy.amount.clone();
+            }
+            TopUpSource::ManageCanister => self.draw_allowance(&policy.amount, available)?,
+        }
+        self.spent_today += policy.amount.clone();
+        Ok(policy.amount)
+    }
+
+    // Give back a reservation whose deposit call failed
+    pub fn release(&mut self, amount: Nat, source: TopUpSource) {
+        if self.spent_today >= amount {
+            self.spent_today -= amount.clone();
+        }
+        self.refund(amount, source);
+    }
+
+    pub fn add_record(&mut self, record: TopUpRecord) {
+        self.records.push(record);
+        if self.records.len() > constant::TOP_UP_RECORD_LIMIT {
+            self.records.remove(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_cycle {
+    use super::*;
+
+    fn new_project_cycle(source: TopUpSource) -> ProjectCycle {
+        ProjectCycle {
+            policy: Some(TopUpPolicy {
+                source: source,
+                amount: Nat::from(100u64),
+                daily_limit: Nat::from(250u64),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_reserve_daily_limit() {
+        let mut project_cycle = new_project_cycle(TopUpSource::ProjectBudget);
+        project_cycle.deposit(Nat::from(1000u64));
+        assert!(project_cycle.reserve(0, 0).is_ok());
+        assert!(project_cycle.reserve(0, 0).is_ok());
+        assert!(project_cycle.reserve(0, 0).is_err());
+        assert_eq!(project_cycle.budget, Nat::from(800u64));
+
+        assert!(project_cycle.reserve(DAY_NANOS, 0).is_ok());
+        assert_eq!(project_cycle.spent_today, Nat::from(100u64));
+    }
+
+    #[test]
+    fn test_reserve_source_balance() {
+        let mut project_cycle = new_project_cycle(TopUpSource::ProjectBudget);
+        assert!(project_cycle.reserve(0, u128::MAX).is_err());
+
+        let mut project_cycle = new_project_cycle(TopUpSource::ManageCanister);
+        assert!(project_cycle.reserve(0, u128::MAX).is_err());
+        project_cycle.manage_allowance = Nat::from(100u64);
+        assert!(project_cycle.reserve(0, 0).is_err());
+        let amount = project_cycle
+            .reserve(0, constant::MANAGE_CANISTER_CYCLE_RESERVE + 100)
+            .unwrap();
+        assert_eq!(project_cycle.manage_allowance, Nat::default());
+        project_cycle.release(amount, TopUpSource::ManageCanister);
+        assert_eq!(project_cycle.spent_today, Nat::default());
+        assert_eq!(project_cycle.manage_allowance, Nat::from(100u64));
+    }
+}
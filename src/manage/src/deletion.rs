@@ -0,0 +1,85 @@
+use crate::constant;
+use crate::error::ManageError;
+use ic_cdk::export::candid::{CandidType, Deserialize};
+use ic_cdk::export::Principal;
+
+// Remaining cycles of a deleted canister are sent to target by a drain module from the
+// project registry. The manage canister reinstalls the drain module on the stopped canister,
+// starts it and calls its `withdraw_cycles : (principal) -> ()` method before deleting it
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct CyclesWithdrawal {
+    pub target: Principal,
+    pub module_name: String,
+    pub module_version: String,
+}
+
+#[derive(CandidType, Debug, Deserialize, Clone, PartialEq, Eq)]
+pub enum DeletionStatus {
+    // Waiting for the cooldown to end and for a confirmation, can be cancelled
+    Pending,
+    // Confirmed, the canister is being stopped, drained and deleted
+    InProgress,
+}
+
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct DeletionRequest {
+    pub canister: Principal,
+    pub account: Principal,
+    pub group_id: u64,
+    pub project_id: u64,
+    pub requester: Principal,
+    pub request_time: u64,
+    pub withdrawal: Option<CyclesWithdrawal>,
+    pub status: DeletionStatus,
+    // Error of the last confirmation, the request goes back to pending when a confirmation fails
+    pub last_error: Option<ManageError>,
+}
+
+impl DeletionRequest {
+    pub fn new(
+        canister: Principal,
+        account: Principal,
+        group_id: u64,
+        project_id: u64,
+        requester: Principal,
+        request_time: u64,
+        withdrawal: Option<CyclesWithdrawal>,
+    ) -> Self {
+        Self {
+            canister: canister,
+            account: account,
+            group_id: group_id,
+            project_id: project_id,
+            requester: requester,
+            request_time: request_time,
+            withdrawal: withdrawal,
+            status: DeletionStatus::Pending,
+            last_error: None,
+        }
+    }
+
+    pub fn confirmable_after(&self) -> u64 {
+        self.request_time + constant::DELETION_COOLDOWN
+    }
+
+    pub fn confirm(&mut self, now: u64) -> Result<(), ManageError> {
+        if self.status != DeletionStatus::Pending {
+            return Err(ManageError::InvalidState(
+                "deletion is already in progress".to_string(),
+            ));
+        }
+        if now < self.confirmable_after() {
+            return Err(ManageError::InvalidState(format!(
+                "deletion can be confirmed after {}",
+                self.confirmable_after()
+            )));
+        }
+        self.status = DeletionStatus::InProgress;
+        Ok(())
+    }
+
+    pub fn fail(&mut self, err: ManageError) {
+        self.status = DeletionStatus::Pending;
+        self.last_error = Some(err);
+    }
+}
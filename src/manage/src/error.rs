@@ -0,0 +1,91 @@
+use crate::authority::Authority;
+use crate::permission::Permission;
+use ic_cdk::api::call::RejectionCode;
+use ic_cdk::export::candid::{CandidType, Deserialize};
+
+#[derive(CandidType, Debug, Deserialize, Clone, PartialEq, Eq)]
+pub enum ResourceKind {
+    User,
+    Group,
+    Project,
+    Member,
+    Canister,
+    Controller,
+    Upload,
+    Module,
+    InstallRecord,
+    Rollout,
+    Deletion,
+    TopUpPolicy,
+    Role,
+    Transfer,
+    Invitation,
+}
+
+// Error returned by every endpoint of the manage canister
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub enum ManageError {
+    NotFound {
+        kind: ResourceKind,
+        id: String,
+    },
+    AlreadyExists {
+        kind: ResourceKind,
+        id: String,
+    },
+    // actual is None when the caller is not a member
+    PermissionDenied {
+        required: Permission,
+        actual: Option<Authority>,
+    },
+    // Only the owner of the account can do this
+    NotOwner,
+    MemberExpired,
+    // The caller is not the principal that started the operation, such as an upload
+    NotInitiator,
+    // Neither the account nor the caller controls the canister
+    NotController,
+    ManagementCallFailed {
+        method: String,
+        code: u8,
+        msg: String,
+    },
+    HashMismatch {
+        expected: Vec<u8>,
+        actual: Option<Vec<u8>>,
+    },
+    InsufficientCycles(String),
+    InvalidState(String),
+    InvalidArgument(String),
+}
+
+impl ManageError {
+    pub fn not_found(kind: ResourceKind, id: impl ToString) -> Self {
+        ManageError::NotFound {
+            kind: kind,
+            id: id.to_string(),
+        }
+    }
+
+    pub fn already_exists(kind: ResourceKind, id: impl ToString) -> Self {
+        ManageError::AlreadyExists {
+            kind: kind,
+            id: id.to_string(),
+        }
+    }
+
+    pub fn permission_denied(required: Permission, actual: Option<Authority>) -> Self {
+        ManageError::PermissionDenied {
+            required: required,
+            actual: actual,
+        }
+    }
+
+    pub fn management_call_failed(method: &str, code: RejectionCode, msg: String) -> Self {
+        ManageError::ManagementCallFailed {
+            method: method.to_string(),
+            code: code as u8,
+            msg: msg,
+        }
+    }
+}
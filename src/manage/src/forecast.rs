@@ -0,0 +1,135 @@
+use crate::monitor::StatusSnapshot;
+use crate::util;
+use ic_cdk::export::candid::Nat;
+use ic_cdk::export::candid::{CandidType, Deserialize};
+use ic_cdk::export::Principal;
+
+const DAY_NANOS: u128 = 24 * 60 * 60 * 1_000_000_000;
+const DAY_SECONDS: u128 = 24 * 60 * 60;
+
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct CycleForecast {
+    pub group_id: u64,
+    pub project_id: u64,
+    pub canister: Principal,
+    pub cycles: Nat,
+    // Cycles burned per day, averaged over the status history of the canister
+    pub burn_rate: Nat,
+    // Nanoseconds until the balance reaches the freezing threshold,
+    // None if the canister did not burn any cycles in its history
+    pub time_until_frozen: Option<u64>,
+    // Nanoseconds until the balance falls below the project's canister_cycle_floor
+    pub time_until_floor: Option<u64>,
+}
+
+impl CycleForecast {
+    pub fn new(
+        group_id: u64,
+        project_id: u64,
+        canister: Principal,
+        history: &[StatusSnapshot],
+        floor: &Nat,
+    ) -> Option<Self> {
+        let latest = history.last()?;
+        let cycles = util::nat_to_u128(&latest.cycles);
+        let rate = burn_rate(history);
+        // The freezing threshold is the number of seconds of idle burn the canister must keep
+        let freezing_cycles = match &latest.freezing_threshold {
+            None => 0,
+            Some(threshold) => util::nat_to_u128(threshold).saturating_mul(rate) / DAY_SECONDS,
+        };
+        Some(Self {
+            group_id: group_id,
+            project_id: project_id,
+            canister: canister,
+            cycles: latest.cycles.clone(),
+            burn_rate: Nat::from(rate),
+            time_until_frozen: time_until(cycles, freezing_cycles, rate),
+            time_until_floor: time_until(cycles, util::nat_to_u128(floor), rate),
+        })
+    }
+
+    fn urgency(&self) -> u64 {
+        let frozen = self.time_until_frozen.unwrap_or(u64::MAX);
+        let floor = self.time_until_floor.unwrap_or(u64::MAX);
+        frozen.min(floor)
+    }
+}
+
+// Only decreases between two snapshots count as burn, so top-ups do not hide the consumption
+pub fn burn_rate(history: &[StatusSnapshot]) -> u128 {
+    if history.len() < 2 {
+        return 0;
+    }
+    let span = (history[history.len() - 1].time - history[0].time) as u128;
+    if span == 0 {
+        return 0;
+    }
+    let burned: u128 = history
+        .windows(2)
+        .map(|pair| {
+            let before = util::nat_to_u128(&pair[0].cycles);
+            let after = util::nat_to_u128(&pair[1].cycles);
+            before.saturating_sub(after)
+        })
+        .fold(0, |total, burned| total.saturating_add(burned));
+    burned.saturating_mul(DAY_NANOS) / span
+}
+
+fn time_until(cycles: u128, target: u128, rate: u128) -> Option<u64> {
+    if rate == 0 {
+        return None;
+    }
+    if cycles <= target {
+        return Some(0);
+    }
+    let nanos = (cycles - target).saturating_mul(DAY_NANOS) / rate;
+    Some(u64::try_from(nanos).unwrap_or(u64::MAX))
+}
+
+// The canisters that run out of cycles first come first
+pub fn sort_by_urgency(forecasts: &mut [CycleForecast]) {
+    forecasts.sort_by_key(|forecast| forecast.urgency());
+}
+
+#[cfg(test)]
+mod test_forecast {
+    use super::*;
+    use crate::manage::CanisterStatus;
+
+    fn snapshot(time: u64, cycles: u64) -> StatusSnapshot {
+        StatusSnapshot {
+            time: time,
+            status: CanisterStatus::Running,
+            memory_size: Nat::default(),
+            cycles: Nat::from(cycles),
+            module_hash: None,
+            freezing_threshold: Some(Nat::from(DAY_SECONDS as u64)),
+        }
+    }
+
+    #[test]
+    fn test_burn_rate_ignores_top_up() {
+        let day = DAY_NANOS as u64;
+        let history = vec![
+            snapshot(0, 1000),
+            snapshot(day, 900),
+            snapshot(day * 2, 2000),
+            snapshot(day * 4, 1800),
+        ];
+        assert_eq!(burn_rate(&history), 75);
+        assert_eq!(burn_rate(&history[..1]), 0);
+    }
+
+    #[test]
+    fn test_forecast() {
+        let day = DAY_NANOS as u64;
+        let history = vec![snapshot(0, 1000), snapshot(day, 900)];
+        let canister = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        let forecast = CycleForecast::new(1, 1, canister, &history, &Nat::from(500u64)).unwrap();
+        assert_eq!(forecast.burn_rate, Nat::from(100u64));
+        // one day of idle burn is reserved by the freezing threshold
+        assert_eq!(forecast.time_until_frozen, Some(day * 8));
+        assert_eq!(forecast.time_until_floor, Some(day * 4));
+    }
+}
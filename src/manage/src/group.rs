@@ -1,7 +1,12 @@
 use crate::authority::Authority;
-use crate::manage::{CanisterSettings, CanisterStatusResponse, InstallCodeMode, ManageCanister};
+use crate::error::{ManageError, ResourceKind};
+use crate::manage::{CanisterSettings, CanisterStatusResponse, Controllability, InstallCodeMode};
 use crate::member::Member;
+use crate::membership::MembershipInfo;
+use crate::permission::Permission;
 use crate::project::Project;
+use crate::registry::InstalledModule;
+use crate::role::Role;
 use crate::types::Profile;
 use candid::CandidType;
 use ic_cdk::api::caller;
@@ -20,6 +25,10 @@ pub struct Group {
     pub projects: HashMap<u64, Project>,
     pub members: HashMap<Principal, Member>,
     pub url: String,
+    // Custom roles by name, usable by the members of the group and of its projects
+    pub roles: HashMap<String, Role>,
+    // Account holding the group, maintained by the manage canister
+    pub owner: Option<Principal>,
 }
 
 impl Group {
@@ -53,59 +62,313 @@ impl Group {
             projects: project,
             members: member,
             url: url,
+            roles: HashMap::new(),
+            owner: None,
         }
     }
 
-    pub fn identity_check(&self, opt: Authority, sender: Principal) -> Result<(), String> {
-        match self.members.get(&sender) {
-            None => {
-                return Err("not in the group member list".to_string());
+    // Permissions given by a built-in authority and an optional custom role of this group
+    pub fn permissions(&self, authority: Authority, role: Option<&String>) -> Vec<Permission> {
+        let mut permissions = authority.permissions();
+        if let Some(role) = role.and_then(|name| self.roles.get(name)) {
+            for permission in role.permissions.iter() {
+                if !permissions.contains(permission) {
+                    permissions.push(*permission);
+                }
+            }
+        }
+        permissions
+    }
+
+    fn member_check(
+        &self,
+        member: &Member,
+        role: Option<&String>,
+        opt: Permission,
+    ) -> Result<(), ManageError> {
+        if !self.permissions(member.authority, role).contains(&opt) {
+            return Err(ManageError::permission_denied(opt, Some(member.authority)));
+        }
+        Self::expiration_check(member)
+    }
+
+    fn expiration_check(member: &Member) -> Result<(), ManageError> {
+        if member.expiration_time.is_some() && !member.is_active(ic_cdk::api::time()) {
+            return Err(ManageError::MemberExpired);
+        }
+        Ok(())
+    }
+
+    pub fn has_expired_members(&self, now: u64) -> bool {
+        self.members.values().any(|member| !member.is_active(now))
+            || self.projects.values().any(|project| {
+                project
+                    .members
+                    .values()
+                    .any(|member| !member.is_active(now))
+            })
+    }
+
+    // Removes the expired members of the group and of its projects,
+    // returns the project of each removed member, None for the group itself
+    pub fn remove_expired_members(&mut self, now: u64) -> Vec<(Option<u64>, Principal)> {
+        let mut removed = Vec::new();
+        self.members.retain(|identity, member| {
+            if !member.is_active(now) {
+                removed.push((None, *identity));
             }
-            Some(member) => {
-                if !Authority::authority_check(member.authority, opt) {
-                    return Err(format!("permission verification failed: user permissions: {:?},opt permissions: {:?}",member.authority,opt) );
+            member.is_active(now)
+        });
+        for (project_id, project) in self.projects.iter_mut() {
+            project.members.retain(|identity, member| {
+                if !member.is_active(now) {
+                    removed.push((Some(*project_id), *identity));
                 }
-                if let Some(expir) = member.expiration_time {
-                    if expir < ic_cdk::api::time() {
-                        return Err("Identity expiration".to_string());
+                member.is_active(now)
+            });
+        }
+        for (project_id, member) in removed.iter() {
+            match project_id {
+                None => self.remove_group_canister_acls(*member),
+                Some(project_id) => {
+                    if let Some(project) = self.projects.get_mut(project_id) {
+                        project.remove_canister_acls(*member);
                     }
                 }
-                Ok(())
             }
         }
+        removed
+    }
+
+    // Drops the canister acl entries of a principal leaving the group,
+    // except in the projects it is still a member of
+    fn remove_group_canister_acls(&mut self, member: Principal) {
+        for project in self.projects.values_mut() {
+            if !project.members.contains_key(&member) {
+                project.remove_canister_acls(member);
+            }
+        }
+    }
+
+    // Active members of the group and its projects whose expiration falls within window from now
+    pub fn expiring_members(&self, now: u64, window: u64) -> Vec<(Option<u64>, Member)> {
+        let mut expiring: Vec<(Option<u64>, Member)> = self
+            .members
+            .values()
+            .filter(|member| member.expires_within(now, window))
+            .map(|member| (None, member.clone()))
+            .collect();
+        for (project_id, project) in self.projects.iter() {
+            for member in project.members.values() {
+                if member.expires_within(now, window) {
+                    expiring.push((Some(*project_id), member.clone()));
+                }
+            }
+        }
+        expiring
+    }
+
+    // Permissions can only be handed out by a sender holding them itself,
+    // in the group or in the project when project_id is set
+    pub fn grant_check(
+        &self,
+        project_id: Option<u64>,
+        permissions: &[Permission],
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        for permission in permissions {
+            self.resolve(project_id, None, *permission, sender)?;
+        }
+        Ok(())
+    }
+
+    pub fn role_grant_check(
+        &self,
+        project_id: Option<u64>,
+        role: &Option<String>,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        match role.as_ref().and_then(|name| self.roles.get(name)) {
+            None => Ok(()),
+            Some(role) => self.grant_check(project_id, &role.permissions, sender),
+        }
+    }
+
+    // The permissions handed out to a new member with its authority and role
+    pub fn member_grant_check(
+        &self,
+        project_id: Option<u64>,
+        member: &Member,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        let permissions = self.permissions(member.authority, member.role.as_ref());
+        self.grant_check(project_id, &permissions, sender)
+    }
+
+    pub fn role_check(&self, role: &Option<String>) -> Result<(), ManageError> {
+        if let Some(name) = role {
+            if !self.roles.contains_key(name) {
+                return Err(ManageError::not_found(ResourceKind::Role, name));
+            }
+        }
+        Ok(())
+    }
+
+    // The permission resolver used by every check of the group, its projects and their canisters.
+    // The owner of the group holds every permission, for anyone else the most specific entry
+    // of the sender wins:
+    // 1. its entry in the acl of the canister, honoured while it is an active member of the
+    //    project or the group
+    // 2. its member entry in the project, with the group role when it sets none
+    // 3. its member entry in the group, inherited by every project of the group
+    fn resolve(
+        &self,
+        project_id: Option<u64>,
+        canister: Option<Principal>,
+        opt: Permission,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        let project = match project_id {
+            None => None,
+            Some(project_id) => match self.projects.get(&project_id) {
+                None => return Err(ManageError::not_found(ResourceKind::Project, project_id)),
+                Some(project) => Some(project),
+            },
+        };
+        if self.owner == Some(sender) {
+            return Ok(());
+        }
+        let entry = self.member_entry(project, sender);
+        if let (Some(project), Some(canister)) = (project, canister) {
+            if let Some(permissions) = project
+                .canister_acls
+                .get(&canister)
+                .and_then(|acl| acl.get(&sender))
+            {
+                match entry {
+                    None => return Err(ManageError::permission_denied(opt, None)),
+                    Some((member, _)) => Self::expiration_check(member)?,
+                }
+                if !permissions.contains(&opt) {
+                    return Err(ManageError::permission_denied(opt, None));
+                }
+                return Ok(());
+            }
+        }
+        match entry {
+            None => Err(ManageError::permission_denied(opt, None)),
+            Some((member, role)) => self.member_check(member, role, opt),
+        }
+    }
+
+    // Steps 2 and 3 of the resolver: the member entry of sender and the role it uses,
+    // an expired project entry falls back to the group entry
+    fn member_entry<'a>(
+        &'a self,
+        project: Option<&'a Project>,
+        sender: Principal,
+    ) -> Option<(&'a Member, Option<&'a String>)> {
+        let group_member = self.members.get(&sender);
+        let group_role = group_member.and_then(|member| member.role.as_ref());
+        match project
+            .and_then(|project| project.members.get(&sender))
+            .filter(|member| Self::expiration_check(member).is_ok())
+        {
+            Some(member) => Some((member, member.role.as_ref().or(group_role))),
+            None => group_member.map(|member| (member, group_role)),
+        }
+    }
+
+    // The effective authority of identity in the group, or in one of its projects when project_id
+    // is set, None when it is not an active member there
+    pub fn membership_info(
+        &self,
+        owner: Principal,
+        project_id: Option<u64>,
+        identity: Principal,
+        now: u64,
+    ) -> Option<MembershipInfo> {
+        let project = match project_id {
+            None => None,
+            Some(project_id) => Some(self.projects.get(&project_id)?),
+        };
+        let (member, role) = self.member_entry(project, identity)?;
+        if !member.is_active(now) {
+            return None;
+        }
+        Some(MembershipInfo {
+            owner: owner,
+            group_id: self.id,
+            group_name: self.name.clone(),
+            project_id: project_id,
+            project_name: project.map(|project| project.name.clone()),
+            authority: member.authority,
+            role: role.cloned(),
+            permissions: self.permissions(member.authority, role),
+            expiration_time: member.expiration_time,
+        })
+    }
+
+    pub fn identity_check(&self, opt: Permission, sender: Principal) -> Result<(), ManageError> {
+        self.resolve(None, None, opt, sender)
     }
 
-    pub fn add_project(&mut self, project: Project, sender: Principal) -> Result<(), String> {
-        self.identity_check(Authority::Operational, sender)?;
+    pub fn add_project(
+        &mut self,
+        mut project: Project,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        self.identity_check(Permission::ManageProjects, sender)?;
         if self.projects.contains_key(&project.id) {
-            return Err("project id already exists".to_string());
+            return Err(ManageError::already_exists(
+                ResourceKind::Project,
+                project.id,
+            ));
+        }
+        if project.in_group != self.id {
+            return Err(ManageError::InvalidArgument(format!(
+                "project {} is not in group {}",
+                project.id, self.id
+            )));
         }
+        // Members and acls are only added through invitations, the creator keeps its group entry
+        project.members.clear();
+        project.canister_acls.clear();
+        project.installed_modules.clear();
+        project.controllability.clear();
+        project.create_by = sender;
         self.projects.insert(project.id, project);
         Ok(())
     }
 
-    pub fn remove_project(&mut self, project_id: u64, sender: Principal) -> Result<(), String> {
-        self.identity_check(Authority::Operational, sender)?;
+    pub fn remove_project(
+        &mut self,
+        project_id: u64,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        self.identity_check(Permission::ManageProjects, sender)?;
         self.projects.remove(&project_id);
 
         Ok(())
     }
 
-    pub fn add_member(&mut self, member: Member) -> Result<(), String> {
+    pub fn add_member(&mut self, member: Member) -> Result<(), ManageError> {
+        self.role_check(&member.role)?;
         self.members.insert(member.identity, member);
         Ok(())
     }
 
-    pub fn remove_member(&mut self, member: Principal) -> Result<(), String> {
+    pub fn remove_member(&mut self, member: Principal) -> Result<(), ManageError> {
         self.members.remove(&member);
+        self.remove_group_canister_acls(member);
         Ok(())
     }
 
     pub fn get_group_projects_info(
         &self,
         sender: Principal,
-    ) -> Result<Option<Vec<Project>>, String> {
-        self.identity_check(Authority::Read, sender)?;
+    ) -> Result<Option<Vec<Project>>, ManageError> {
+        self.identity_check(Permission::ViewStatus, sender)?;
         return Ok(Some(self.projects.values().map(|i| i.clone()).collect()));
     }
 
@@ -113,9 +376,9 @@ impl Group {
         &mut self,
         member: Principal,
         authority: Authority,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
         match self.members.get_mut(&member) {
-            None => return Err("member information not found".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Member, member)),
             Some(data) => {
                 data.authority = authority;
                 return Ok(());
@@ -123,29 +386,199 @@ impl Group {
         }
     }
 
+    pub fn update_member_role(
+        &mut self,
+        member: Principal,
+        role: Option<String>,
+    ) -> Result<(), ManageError> {
+        self.role_check(&role)?;
+        match self.members.get_mut(&member) {
+            None => return Err(ManageError::not_found(ResourceKind::Member, member)),
+            Some(data) => {
+                data.role = role;
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn update_member_expiration(
+        &mut self,
+        member: Principal,
+        expiration_time: Option<u64>,
+    ) -> Result<(), ManageError> {
+        match self.members.get_mut(&member) {
+            None => return Err(ManageError::not_found(ResourceKind::Member, member)),
+            Some(data) => {
+                data.expiration_time = expiration_time;
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn update_project_member_expiration(
+        &mut self,
+        project_id: u64,
+        member: Principal,
+        expiration_time: Option<u64>,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        self.project_identity_check(project_id, Permission::ManageMembers, sender)?;
+        match self.projects.get_mut(&project_id) {
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => project.update_member_expiration(member, expiration_time),
+        }
+    }
+
     pub fn update_project_member_authority(
         &mut self,
         project_id: u64,
         member: Principal,
         authority: Authority,
         sender: Principal,
-    ) -> Result<(), String> {
-        self.identity_check(Authority::Operational, sender)?;
+    ) -> Result<(), ManageError> {
+        self.project_identity_check(project_id, Permission::ManageMembers, sender)?;
+        self.grant_check(Some(project_id), &authority.permissions(), sender)?;
         match self.projects.get_mut(&project_id) {
-            None => Err("Project does not exist".to_string()),
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
             Some(project) => project.update_member_authority(member, authority),
         }
     }
 
+    pub fn update_project_member_role(
+        &mut self,
+        project_id: u64,
+        member: Principal,
+        role: Option<String>,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        self.project_identity_check(project_id, Permission::ManageMembers, sender)?;
+        self.role_check(&role)?;
+        self.role_grant_check(Some(project_id), &role, sender)?;
+        match self.projects.get_mut(&project_id) {
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => project.update_member_role(member, role),
+        }
+    }
+
+    pub fn create_role(&mut self, role: Role) -> Result<(), ManageError> {
+        if self.roles.contains_key(&role.name) {
+            return Err(ManageError::already_exists(ResourceKind::Role, role.name));
+        }
+        self.roles.insert(role.name.clone(), role);
+        Ok(())
+    }
+
+    pub fn update_role(&mut self, role: Role) -> Result<(), ManageError> {
+        match self.roles.get_mut(&role.name) {
+            None => Err(ManageError::not_found(ResourceKind::Role, role.name)),
+            Some(data) => {
+                *data = role;
+                Ok(())
+            }
+        }
+    }
+
+    // A role can only be deleted once no group or project member has it
+    pub fn delete_role(&mut self, name: &str) -> Result<(), ManageError> {
+        if !self.roles.contains_key(name) {
+            return Err(ManageError::not_found(ResourceKind::Role, name));
+        }
+        let assigned = |member: &Member| member.role.as_deref() == Some(name);
+        if let Some(member) = self.members.values().find(|member| assigned(member)) {
+            return Err(ManageError::InvalidState(format!(
+                "role {} is still assigned to group member {}",
+                name, member.identity
+            )));
+        }
+        for project in self.projects.values() {
+            if let Some(member) = project.members.values().find(|member| assigned(member)) {
+                return Err(ManageError::InvalidState(format!(
+                    "role {} is still assigned to member {} of project {}",
+                    name, member.identity, project.id
+                )));
+            }
+        }
+        self.roles.remove(name);
+        Ok(())
+    }
+
+    pub fn project_identity_check(
+        &self,
+        project_id: u64,
+        opt: Permission,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        self.resolve(Some(project_id), None, opt, sender)
+    }
+
+    pub fn canister_identity_check(
+        &self,
+        project_id: u64,
+        canister: Principal,
+        opt: Permission,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        self.resolve(Some(project_id), Some(canister), opt, sender)
+    }
+
+    pub fn update_canister_acl(
+        &mut self,
+        project_id: u64,
+        canister: Principal,
+        principal: Principal,
+        permissions: Option<Vec<Permission>>,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        self.project_identity_check(project_id, Permission::ManageMembers, sender)?;
+        if let Some(permissions) = &permissions {
+            self.grant_check(Some(project_id), permissions, sender)?;
+        }
+        match self.projects.get_mut(&project_id) {
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => project.update_canister_acl(canister, principal, permissions),
+        }
+    }
+
+    pub fn set_installed_module(
+        &mut self,
+        project_id: u64,
+        canister: Principal,
+        module: Option<InstalledModule>,
+    ) -> Result<(), ManageError> {
+        match self.projects.get_mut(&project_id) {
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => {
+                project.set_installed_module(canister, module);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn set_controllability(
+        &mut self,
+        project_id: u64,
+        canister: Principal,
+        controllability: Controllability,
+    ) -> Result<(), ManageError> {
+        match self.projects.get_mut(&project_id) {
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => {
+                project.set_controllability(canister, controllability);
+                Ok(())
+            }
+        }
+    }
+
     pub fn update_git_repo_url(
         &mut self,
         project_id: u64,
         git: &str,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
+        self.project_identity_check(project_id, Permission::EditMetadata, sender)?;
         match self.projects.get_mut(&project_id) {
-            None => Err("Project does not exist".to_string()),
-            Some(project) => project.update_git_repo_url(git, sender),
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => project.update_git_repo_url(git),
         }
     }
 
@@ -154,11 +587,11 @@ impl Group {
         project_id: u64,
         floor: Nat,
         sender: Principal,
-    ) -> Result<(), String> {
-        self.identity_check(Authority::Write, sender)?;
+    ) -> Result<(), ManageError> {
+        self.project_identity_check(project_id, Permission::TopUp, sender)?;
         match self.projects.get_mut(&project_id) {
-            None => Err("Project does not exist".to_string()),
-            Some(project) => project.update_canister_cycle_floor(floor, sender),
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => project.update_canister_cycle_floor(floor),
         }
     }
 
@@ -167,10 +600,11 @@ impl Group {
         project_id: u64,
         visibility: Profile,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
+        self.project_identity_check(project_id, Permission::EditMetadata, sender)?;
         match self.projects.get_mut(&project_id) {
-            None => Err("Project does not exist".to_string()),
-            Some(project) => project.update_visibility(visibility, sender),
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => project.update_visibility(visibility),
         }
     }
 
@@ -179,10 +613,11 @@ impl Group {
         project_id: u64,
         description: &str,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
+        self.project_identity_check(project_id, Permission::EditMetadata, sender)?;
         match self.projects.get_mut(&project_id) {
-            None => Err("Project does not exist".to_string()),
-            Some(project) => project.update_description(description, sender),
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => project.update_description(description),
         }
     }
 
@@ -191,10 +626,12 @@ impl Group {
         project_id: u64,
         member: Member,
         sender: Principal,
-    ) -> Result<(), String> {
-        self.identity_check(Authority::Operational, sender)?;
+    ) -> Result<(), ManageError> {
+        self.project_identity_check(project_id, Permission::ManageMembers, sender)?;
+        self.role_check(&member.role)?;
+        self.member_grant_check(Some(project_id), &member, sender)?;
         match self.projects.get_mut(&project_id) {
-            None => Err("Project does not exist".to_string()),
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
             Some(project) => project.add_member(member),
         }
     }
@@ -204,12 +641,12 @@ impl Group {
         project_id: u64,
         member: Principal,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
         if member != sender {
-            self.identity_check(Authority::Operational, sender)?;
+            self.project_identity_check(project_id, Permission::ManageMembers, sender)?;
         }
         match self.projects.get_mut(&project_id) {
-            None => Err("Project does not exist".to_string()),
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
             Some(project) => project.remove_member(member),
         }
     }
@@ -219,10 +656,21 @@ impl Group {
         project_id: u64,
         canister: Principal,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
+        self.project_identity_check(project_id, Permission::ManageCanisters, sender)?;
+        self.register_canister(project_id, canister)
+    }
+
+    // Adds a canister to its project without a permission check, once the manage canister
+    // created it for a sender checked before
+    pub fn register_canister(
+        &mut self,
+        project_id: u64,
+        canister: Principal,
+    ) -> Result<(), ManageError> {
         match self.projects.get_mut(&project_id) {
-            None => Err("Project does not exist".to_string()),
-            Some(project) => project.add_canister(canister, sender),
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => project.add_canister(canister),
         }
     }
 
@@ -231,10 +679,21 @@ impl Group {
         project_id: u64,
         canister: Principal,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
+        self.project_identity_check(project_id, Permission::ManageCanisters, sender)?;
+        self.unregister_canister(project_id, canister)
+    }
+
+    // Drops a canister from its project without a permission check, once the manage canister
+    // deleted it
+    pub fn unregister_canister(
+        &mut self,
+        project_id: u64,
+        canister: Principal,
+    ) -> Result<(), ManageError> {
         match self.projects.get_mut(&project_id) {
-            None => Err("Project does not exist".to_string()),
-            Some(project) => project.remove_canister(canister, sender),
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => project.remove_canister(canister),
         }
     }
 
@@ -243,11 +702,39 @@ impl Group {
         project_id: u64,
         canister: Principal,
         sender: Principal,
-    ) -> Result<impl Future<Output = Result<(CanisterStatusResponse, Nat), String>>, String> {
+    ) -> Result<impl Future<Output = Result<(CanisterStatusResponse, Nat), ManageError>>, ManageError>
+    {
+        if Profile::Private == self.visibility {
+            self.canister_identity_check(project_id, canister, Permission::ViewStatus, sender)?;
+        }
+        match self.projects.get(&project_id) {
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => project.get_canister_status(canister),
+        }
+    }
+
+    // Same visibility rule as get_canister_status: members of a private group need read permission
+    pub fn canister_read_check(
+        &self,
+        project_id: u64,
+        canister: Principal,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
         match self.projects.get(&project_id) {
-            None => Err("Project does not exist".to_string()),
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
             Some(project) => {
-                project.get_canister_status(canister, sender, Profile::Private == self.visibility)
+                if !project.canisters.contains(&canister) {
+                    return Err(ManageError::not_found(ResourceKind::Canister, canister));
+                }
+                if Profile::Private == self.visibility {
+                    self.canister_identity_check(
+                        project_id,
+                        canister,
+                        Permission::ViewStatus,
+                        sender,
+                    )?;
+                }
+                Ok(())
             }
         }
     }
@@ -257,10 +744,25 @@ impl Group {
         project_id: u64,
         canister: Principal,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
+        self.project_identity_check(project_id, Permission::ManageSettings, sender)?;
         match self.projects.get(&project_id) {
-            None => Err("Project does not exist".to_string()),
-            Some(project) => project.set_canister_controller(canister, sender).await,
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => project.set_canister_controller(canister).await,
+        }
+    }
+
+    pub fn update_project_canister_settings(
+        &self,
+        project_id: u64,
+        canister: Principal,
+        settings: CanisterSettings,
+        sender: Principal,
+    ) -> Result<impl Future<Output = Result<(), ManageError>>, ManageError> {
+        self.project_identity_check(project_id, Permission::ManageSettings, sender)?;
+        match self.projects.get(&project_id) {
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => project.update_canister_settings(canister, settings),
         }
     }
 
@@ -269,10 +771,11 @@ impl Group {
         project_id: u64,
         canister: Principal,
         sender: Principal,
-    ) -> Result<impl Future<Output = Result<(), String>>, String> {
+    ) -> Result<impl Future<Output = Result<(), ManageError>>, ManageError> {
+        self.canister_identity_check(project_id, canister, Permission::StartStop, sender)?;
         match self.projects.get(&project_id) {
-            None => Err("Project does not exist....".to_string()),
-            Some(project) => project.stop_canister(canister, sender),
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => project.stop_canister(canister),
         }
     }
 
@@ -281,10 +784,11 @@ impl Group {
         project_id: u64,
         canister: Principal,
         sender: Principal,
-    ) -> Result<impl Future<Output = Result<(), String>>, String> {
+    ) -> Result<impl Future<Output = Result<(), ManageError>>, ManageError> {
+        self.canister_identity_check(project_id, canister, Permission::StartStop, sender)?;
         match self.projects.get(&project_id) {
-            None => Err("Project does not exist".to_string()),
-            Some(project) => project.start_canister(canister, sender),
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => project.start_canister(canister),
         }
     }
 
@@ -293,10 +797,11 @@ impl Group {
         project_id: u64,
         canister: Principal,
         sender: Principal,
-    ) -> Result<impl Future<Output = Result<(), String>>, String> {
+    ) -> Result<impl Future<Output = Result<(), ManageError>>, ManageError> {
+        self.canister_identity_check(project_id, canister, Permission::Delete, sender)?;
         match self.projects.get(&project_id) {
-            None => Err("Project does not exist".to_string()),
-            Some(project) => project.delete_canister(canister, sender),
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => project.delete_canister(canister),
         }
     }
 
@@ -308,10 +813,11 @@ impl Group {
         wasm: Vec<u8>,
         args: Vec<u8>,
         sender: Principal,
-    ) -> Result<impl Future<Output = Result<(), String>>, String> {
+    ) -> Result<impl Future<Output = Result<(), ManageError>>, ManageError> {
+        self.canister_identity_check(project_id, canister, Permission::InstallCode, sender)?;
         match self.projects.get(&project_id) {
-            None => Err("Project does not exist".to_string()),
-            Some(project) => project.install_code(canister, install_mod, wasm, args, sender),
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+            Some(project) => project.install_code(canister, install_mod, wasm, args),
         }
     }
 
@@ -319,33 +825,14 @@ impl Group {
         &self,
         project_id: u64,
         sender: Principal,
-    ) -> Result<Option<&Project>, String> {
-        match self.visibility {
-            Profile::Public => match self.projects.get(&project_id) {
-                None => Ok(None),
-                Some(project) => match project.visibility {
-                    Profile::Public => Ok(Some(project)),
-                    Profile::Private => {
-                        if let None = project.members.get(&sender) {
-                            return Err("No permission".to_string());
-                        }
-                        Ok(Some(project))
-                    }
-                },
-            },
-            Profile::Private => {
-                if let None = self.members.get(&sender) {
-                    match self.projects.get(&project_id) {
-                        None => return Ok(None),
-                        Some(project) => {
-                            if let None = project.members.get(&sender) {
-                                return Err("No permission".to_string());
-                            }
-                            return Ok(Some(project));
-                        }
-                    }
-                };
-                Ok(self.projects.get(&project_id))
+    ) -> Result<Option<&Project>, ManageError> {
+        match self.projects.get(&project_id) {
+            None => Ok(None),
+            Some(project) => {
+                if Profile::Private == self.visibility || Profile::Private == project.visibility {
+                    self.project_identity_check(project_id, Permission::ViewStatus, sender)?;
+                }
+                Ok(Some(project))
             }
         }
     }
@@ -357,8 +844,8 @@ impl Group {
         visibility: Profile,
         url: String,
         sender: Principal,
-    ) -> Result<(), String> {
-        self.identity_check(Authority::Write, sender)?;
+    ) -> Result<(), ManageError> {
+        self.identity_check(Permission::EditMetadata, sender)?;
         self.name = name;
         self.description = description;
         self.visibility = visibility;
@@ -373,27 +860,13 @@ impl Group {
         description: String,
         visibility: Profile,
         git: String,
-        canister_cycle_floor: Nat,
-        canisters: &[Principal],
         sender: Principal,
-    ) -> Result<(), String> {
-        let mut check = true;
-        if let Ok(()) = self.identity_check(Authority::Write, sender) {
-            check = false;
-        }
+    ) -> Result<(), ManageError> {
+        self.project_identity_check(project_id, Permission::EditMetadata, sender)?;
         match self.projects.get_mut(&project_id) {
-            None => Err("Project does not exist".to_string()),
-
-            Some(project) => project.update_basic_information(
-                name,
-                description,
-                visibility,
-                git,
-                canister_cycle_floor,
-                canisters,
-                sender,
-                check,
-            ),
+            None => Err(ManageError::not_found(ResourceKind::Project, project_id)),
+
+            Some(project) => project.update_basic_information(name, description, visibility, git),
         }
     }
 }
@@ -0,0 +1,47 @@
+use crate::member::Member;
+use ic_cdk::export::candid::{CandidType, Deserialize};
+use ic_cdk::export::Principal;
+
+// Invitation to join a group, or one of its projects when project_id is set.
+// The member is only added once the invitee accepts
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct Invitation {
+    pub id: u64,
+    pub account: Principal,
+    pub group_id: u64,
+    pub project_id: Option<u64>,
+    pub inviter: Principal,
+    pub invite_time: u64,
+    // Member added on acceptance, its identity is the invitee
+    pub member: Member,
+}
+
+impl Invitation {
+    pub fn new(
+        id: u64,
+        account: Principal,
+        group_id: u64,
+        project_id: Option<u64>,
+        inviter: Principal,
+        invite_time: u64,
+        member: Member,
+    ) -> Self {
+        Self {
+            id: id,
+            account: account,
+            group_id: group_id,
+            project_id: project_id,
+            inviter: inviter,
+            invite_time: invite_time,
+            member: member,
+        }
+    }
+
+    pub fn invitee(&self) -> Principal {
+        self.member.identity
+    }
+
+    pub fn is_for(&self, account: Principal, group_id: u64, project_id: Option<u64>) -> bool {
+        self.account == account && self.group_id == group_id && self.project_id == project_id
+    }
+}
@@ -1,36 +1,190 @@
 use ic_cdk_macros::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 mod authority;
 mod constant;
+mod cycle;
+mod deletion;
+mod error;
+mod forecast;
 mod group;
+mod invitation;
 mod manage;
 mod member;
+mod membership;
+mod migration;
+mod monitor;
+mod notification;
 #[macro_use]
 mod operation;
+mod permission;
 mod project;
+mod registry;
+mod role;
+mod rollback;
+mod rollout;
+mod transaction;
+mod transfer;
 mod types;
+mod upload;
 mod user;
 mod util;
 use authority::Authority;
 use candid::CandidType;
+use cycle::{ProjectCycle, TopUpPolicy, TopUpRecord, TopUpSource};
+use deletion::{CyclesWithdrawal, DeletionRequest, DeletionStatus};
+use error::{ManageError, ResourceKind};
+use forecast::CycleForecast;
 use group::Group;
 use ic_cdk::export::candid::{Deserialize, Nat};
 use ic_cdk::export::Principal;
-use manage::{CanisterSettings, CanisterStatusResponse, InstallCodeMode, ManageCanister};
+use invitation::Invitation;
+use manage::{
+    CanisterSettings, CanisterStatus, CanisterStatusResponse, Controllability, CreatedCanister,
+    InstallCodeMode, ManageCanister,
+};
 use member::Member;
+use membership::{Membership, MembershipIndex, MembershipInfo};
+use migration::LegacyUser;
+use monitor::StatusSnapshot;
+use notification::ExpiringMember;
+use permission::Permission;
 use project::Project;
+use registry::{ModuleInfo, ModuleInstall, ModuleRegistry, WasmModule};
+use role::Role;
+use rollback::{InstallRecord, InstallRecordInfo};
+use rollout::{Rollout, RolloutStatus, StepStatus};
 use std::cell::RefCell;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::RwLock;
+use transaction::UserTransaction;
+use transfer::GroupTransfer;
 use types::{Action, Profile};
+use upload::{UploadInfo, UploadSession};
 use user::User;
 
 type User_Storage = HashMap<Principal, User>;
+// (account, group_id, project_id) -> cycle top-up state of the project
+type Cycle_Storage = HashMap<(Principal, u64, u64), ProjectCycle>;
+// (account, group_id, project_id, canister)
+type Monitor_Key = (Principal, u64, u64, Principal);
+// Status history of the canister in the project
+type Monitor_Storage = HashMap<Monitor_Key, Vec<StatusSnapshot>>;
+type Upload_Storage = HashMap<u64, UploadSession>;
+// (account, group_id, project_id) -> modules published to the project
+type Registry_Storage = HashMap<(Principal, u64, u64), ModuleRegistry>;
+// sha256 -> Wasm module, stored once however many registry versions and install records refer to it
+type Wasm_Storage = HashMap<Vec<u8>, Vec<u8>>;
+// canister -> modules recently installed on the canister
+type Install_Storage = HashMap<Principal, Vec<InstallRecord>>;
+type Rollout_Storage = HashMap<u64, Rollout>;
+// canister -> pending or running deletion of the canister
+type Deletion_Storage = HashMap<Principal, DeletionRequest>;
+// (owner, group_id) -> pending ownership transfer of the group
+type Transfer_Storage = HashMap<(Principal, u64), GroupTransfer>;
+type Invitation_Storage = HashMap<u64, Invitation>;
+// (account, group_id, project_id, member)
+type Notice_Key = (Principal, u64, Option<u64>, Principal);
+// Expiration time the member was last notified about
+type Notice_Storage = HashMap<Notice_Key, u64>;
+// Layout of the stable memory, written by pre_upgrade and read by post_upgrade
+type Stable_Data = (
+    Principal,
+    Principal,
+    Vec<(Principal, User)>,
+    Vec<((Principal, u64, u64), ProjectCycle)>,
+    u64,
+    Vec<(Monitor_Key, Vec<StatusSnapshot>)>,
+    u64,
+    Vec<(u64, UploadSession)>,
+    Vec<((Principal, u64, u64), ModuleRegistry)>,
+    Vec<(Principal, Vec<InstallRecord>)>,
+    u64,
+    Vec<(u64, Rollout)>,
+    Vec<(Principal, DeletionRequest)>,
+    Vec<((Principal, u64), GroupTransfer)>,
+    Principal,
+    (
+        u64,
+        Vec<(u64, Invitation)>,
+        Option<Principal>,
+        u64,
+        Vec<(Notice_Key, u64)>,
+        Vec<(Principal, Membership)>,
+        Vec<(Vec<u8>, Vec<u8>)>,
+    ),
+);
+// Layout of the stable memory written by the first release
+type Legacy_Stable_Data = (Principal, Principal, Vec<(Principal, LegacyUser)>);
 static mut OWNER: Principal = Principal::from_slice(&[0]);
+static mut LAST_TOP_UP: u64 = 0;
+static mut TOP_UP_RUNNING: bool = false;
+static mut LAST_MONITOR: u64 = 0;
+static mut MONITOR_RUNNING: bool = false;
+static mut LAST_MEMBER_SWEEP: u64 = 0;
+// Cycles of the manage canister balance reserved by top-ups and canister creations still in flight
+static mut IN_FLIGHT_CYCLES: u128 = 0;
+static mut UPLOAD_ID: u64 = 0;
+static mut ROLLOUT_ID: u64 = 0;
+static mut INVITATION_ID: u64 = 0;
 thread_local! {
     static USER_STORAGE: RefCell<User_Storage> = RefCell::default();
+    static CYCLE_STORAGE: RefCell<Cycle_Storage> = RefCell::default();
+    static MONITOR_STORAGE: RefCell<Monitor_Storage> = RefCell::default();
+    static UPLOAD_STORAGE: RefCell<Upload_Storage> = RefCell::default();
+    static REGISTRY_STORAGE: RefCell<Registry_Storage> = RefCell::default();
+    static WASM_STORAGE: RefCell<Wasm_Storage> = RefCell::default();
+    static INSTALL_STORAGE: RefCell<Install_Storage> = RefCell::default();
+    static ROLLOUT_STORAGE: RefCell<Rollout_Storage> = RefCell::default();
+    static DELETION_STORAGE: RefCell<Deletion_Storage> = RefCell::default();
+    static TRANSFER_STORAGE: RefCell<Transfer_Storage> = RefCell::default();
+    static INVITATION_STORAGE: RefCell<Invitation_Storage> = RefCell::default();
+    static NOTICE_STORAGE: RefCell<Notice_Storage> = RefCell::default();
+    static MEMBERSHIP_INDEX: RefCell<MembershipIndex> = RefCell::default();
+}
+
+// Runs f as one unit of work over USER_STORAGE: the users and groups changed by f, and the
+// changes of the other storages f registered with on_commit, are written back together
+// when it succeeds and all discarded when it fails
+fn with_users<T, F>(f: F) -> Result<T, ManageError>
+where
+    F: FnOnce(&mut UserTransaction) -> Result<T, ManageError>,
+{
+    let (res, commits) = USER_STORAGE.with(|user_storage| {
+        let (res, staged) = {
+            let user_storage = user_storage.borrow();
+            let mut transaction = UserTransaction::new(&user_storage);
+            let res = f(&mut transaction)?;
+            (res, transaction.into_staged())
+        };
+        let commits = staged.apply(&mut user_storage.borrow_mut());
+        Ok((res, commits))
+    })?;
+    for commit in commits {
+        commit();
+    }
+    Ok(res)
+}
+
+// Unit of work changing a single group of a user
+fn with_user<T, F>(account: Principal, group_id: u64, f: F) -> Result<T, ManageError>
+where
+    F: FnOnce(&mut User) -> Result<T, ManageError>,
+{
+    with_users(|transaction| f(transaction.user_mut(account, group_id)?))
+}
+
+// Membership index changes of a unit of work
+fn insert_membership(users: &mut UserTransaction, member: Principal, membership: Membership) {
+    users.on_commit(move || {
+        MEMBERSHIP_INDEX.with(|index| index.borrow_mut().insert(member, membership))
+    });
+}
 
+fn remove_membership(users: &mut UserTransaction, member: Principal, membership: Membership) {
+    users.on_commit(move || {
+        MEMBERSHIP_INDEX.with(|index| index.borrow_mut().remove(member, &membership))
+    });
 }
 
 #[init]
@@ -51,20 +205,68 @@ pub fn update_log_canister(log_canister: Principal) {
     }
 }
 
+#[update]
+pub fn update_image_store_canister(image_store_canister: Principal) {
+    let caller = ic_cdk::api::caller();
+    unsafe {
+        if OWNER != caller {
+            ic_cdk::trap("invalid identity");
+        }
+        constant::IMAGE_STORE_CANISTER = image_store_canister;
+    }
+}
+
+#[update]
+pub fn update_notification_canister(notification_canister: Option<Principal>) {
+    let caller = ic_cdk::api::caller();
+    unsafe {
+        if OWNER != caller {
+            ic_cdk::trap("invalid identity");
+        }
+        constant::NOTIFICATION_CANISTER = notification_canister;
+    }
+}
+
+#[update]
+fn update_expiry_notice(notice: u64) {
+    let caller = ic_cdk::api::caller();
+    unsafe {
+        if OWNER != caller {
+            ic_cdk::trap("invalid identity");
+        }
+        constant::EXPIRY_NOTICE = notice;
+    }
+}
+
 async fn authority_check(canister: Principal, ii: Principal, sender: Principal) {
+    if let Err(err) = controller_check(canister, ii, sender).await {
+        ic_cdk::api::trap(&format!("{:?}", err));
+    }
+}
+
+// The account or the sender must be a controller of the canister
+async fn controller_check(
+    canister: Principal,
+    ii: Principal,
+    sender: Principal,
+) -> Result<(), ManageError> {
     match ManageCanister::get_canister_status(canister, Nat::default()).await {
-        Err(err) => ic_cdk::api::trap(&err),
-        Ok(status) => match status.0.settings.controllers {
-            None => {
-                ic_cdk::api::trap("ii is not a canister controller");
+        Ok((status, _)) => {
+            let controllers = status.settings.controllers.unwrap_or_default();
+            if !controllers.contains(&ii) && !controllers.contains(&sender) {
+                return Err(ManageError::NotController);
             }
-            Some(controllers) => {
-                if !controllers.contains(&ii) && !controllers.contains(&sender) {
-                    ic_cdk::api::trap("no operation permission");
-                }
+            Ok(())
+        }
+        // A non-controller gets the controllers in the reject message
+        Err(ManageError::ManagementCallFailed { msg, .. }) => {
+            if !util::is_controller(msg.clone(), ii) && !util::is_controller(msg, sender) {
+                return Err(ManageError::NotController);
             }
-        },
-    };
+            Ok(())
+        }
+        Err(err) => Err(err),
+    }
 }
 
 #[update]
@@ -73,27 +275,217 @@ async fn get_canister_status(
     group_id: u64,
     project_id: u64,
     canister: Principal,
-) -> Result<(CanisterStatusResponse, Nat), String> {
+) -> Result<(CanisterStatusResponse, Nat), ManageError> {
     let caller = ic_cdk::api::caller();
     let task = USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&ii) {
         None => {
-            return Err("user does not exist".to_string());
+            return Err(ManageError::not_found(ResourceKind::User, ii));
         }
         Some(user) => user.get_canister_status(group_id, project_id, canister, caller),
     })?;
-    futures::join!(task).0
+    let status = futures::join!(task).0;
+    if let Ok((status, _)) = &status {
+        record_canister_status((ii, group_id, project_id, canister), status);
+    }
+    status
+}
+
+// Cycles of the manage canister balance not promised to calls in flight
+fn available_cycles() -> u128 {
+    unsafe { ic_cdk::api::canister_balance128().saturating_sub(IN_FLIGHT_CYCLES) }
+}
+
+// Sets the cycles a project may take from the manage canister balance for top-ups and
+// canister creations with TopUpSource::ManageCanister
+#[update]
+fn update_project_cycle_allowance(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    allowance: Nat,
+) {
+    let caller = ic_cdk::api::caller();
+    unsafe {
+        if OWNER != caller {
+            ic_cdk::trap("invalid identity");
+        }
+    }
+    CYCLE_STORAGE.with(|cycle_storage| {
+        cycle_storage
+            .borrow_mut()
+            .entry((account, group_id, project_id))
+            .or_default()
+            .manage_allowance = allowance;
+    });
+}
+
+#[update]
+fn update_monitor_interval(interval: u64) {
+    let caller = ic_cdk::api::caller();
+    unsafe {
+        if OWNER != caller {
+            ic_cdk::trap("invalid identity");
+        }
+        if !(constant::MIN_MONITOR_INTERVAL..=constant::MAX_MONITOR_INTERVAL).contains(&interval) {
+            ic_cdk::trap("invalid monitor interval");
+        }
+        constant::MONITOR_INTERVAL = interval;
+    }
+}
+
+#[query]
+fn get_canister_status_history(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    canister: Principal,
+) -> Result<Vec<StatusSnapshot>, ManageError> {
+    let caller = ic_cdk::api::caller();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.canister_read_check(group_id, project_id, canister, caller),
+    })?;
+    Ok(MONITOR_STORAGE.with(|monitor_storage| {
+        monitor_storage
+            .borrow()
+            .get(&(account, group_id, project_id, canister))
+            .cloned()
+            .unwrap_or_default()
+    }))
+}
+
+// Forecasts of the given (group_id, project_id, canister, canister_cycle_floor) of account,
+// most urgent first
+fn cycle_forecasts(
+    account: Principal,
+    canisters: Vec<(u64, u64, Principal, Nat)>,
+) -> Vec<CycleForecast> {
+    MONITOR_STORAGE.with(|monitor_storage| {
+        let monitor_storage = monitor_storage.borrow();
+        let mut forecasts: Vec<CycleForecast> = canisters
+            .iter()
+            .filter_map(|(group_id, project_id, canister, floor)| {
+                let history = monitor_storage.get(&(account, *group_id, *project_id, *canister))?;
+                CycleForecast::new(*group_id, *project_id, *canister, history, floor)
+            })
+            .collect();
+        forecast::sort_by_urgency(&mut forecasts);
+        forecasts
+    })
+}
+
+#[query]
+fn get_project_cycle_forecast(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+) -> Result<Vec<CycleForecast>, ManageError> {
+    let caller = ic_cdk::api::caller();
+    let canisters =
+        USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+            None => {
+                return Err(ManageError::not_found(ResourceKind::User, account));
+            }
+            Some(user) => {
+                user.project_identity_check(group_id, project_id, Permission::ViewStatus, caller)?;
+                let project = user.get_project(group_id, project_id)?;
+                Ok(project
+                    .canisters
+                    .iter()
+                    .map(|canister| {
+                        (
+                            group_id,
+                            project_id,
+                            *canister,
+                            project.canister_cycle_floor.clone(),
+                        )
+                    })
+                    .collect())
+            }
+        })?;
+    Ok(cycle_forecasts(account, canisters))
+}
+
+#[query]
+fn get_group_cycle_forecast(
+    account: Principal,
+    group_id: u64,
+) -> Result<Vec<CycleForecast>, ManageError> {
+    let caller = ic_cdk::api::caller();
+    let canisters =
+        USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+            None => {
+                return Err(ManageError::not_found(ResourceKind::User, account));
+            }
+            Some(user) => {
+                user.group_identity_check(group_id, Permission::ViewStatus, caller)?;
+                let mut canisters = Vec::new();
+                for (project_id, project) in user.groups.get(&group_id).unwrap().projects.iter() {
+                    for canister in project.canisters.iter() {
+                        canisters.push((
+                            group_id,
+                            *project_id,
+                            *canister,
+                            project.canister_cycle_floor.clone(),
+                        ));
+                    }
+                }
+                Ok(canisters)
+            }
+        })?;
+    Ok(cycle_forecasts(account, canisters))
+}
+
+fn record_canister_status(key: Monitor_Key, status: &CanisterStatusResponse) {
+    let snapshot = StatusSnapshot::new(ic_cdk::api::time(), status);
+    MONITOR_STORAGE.with(|monitor_storage| {
+        let mut monitor_storage = monitor_storage.borrow_mut();
+        monitor::push_snapshot(monitor_storage.entry(key).or_default(), snapshot);
+    });
+}
+
+async fn monitor_project_canisters() {
+    let keys: Vec<Monitor_Key> = project_canisters()
+        .into_iter()
+        .map(|(account, group_id, project_id, canister, _)| {
+            (account, group_id, project_id, canister)
+        })
+        .collect();
+    // Canisters that are no longer registered in their project are not monitored anymore
+    MONITOR_STORAGE.with(|monitor_storage| {
+        monitor_storage
+            .borrow_mut()
+            .retain(|key, _| keys.contains(key));
+    });
+    let mut canisters: Vec<Principal> = keys.iter().map(|key| key.3).collect();
+    canisters.sort();
+    canisters.dedup();
+    for canister in canisters {
+        if let Ok((status, _)) = ManageCanister::get_canister_status(canister, Nat::default()).await
+        {
+            // A canister registered in several projects is recorded in each of them
+            for key in keys.iter().filter(|key| key.3 == canister) {
+                record_canister_status(*key, &status);
+            }
+        }
+    }
+    unsafe {
+        MONITOR_RUNNING = false;
+    }
 }
 
 #[update]
-fn add_user(name: String, profile: Profile) -> Result<(), String> {
+fn add_user(name: String, profile: Profile) -> Result<(), ManageError> {
     let caller = ic_cdk::caller();
-    USER_STORAGE.with(|user_storage| {
-        if let Some(_) = user_storage.borrow().get(&caller) {
-            return Err("User already exists".to_string());
+    with_users(|users| {
+        if users.contains(caller) {
+            return Err(ManageError::already_exists(ResourceKind::User, caller));
         }
         let create_time = ic_cdk::api::time();
         let user = User::new(name, profile, caller, create_time);
-        user_storage.borrow_mut().insert(caller, user);
+        users.insert(caller, user);
         Ok(())
     })
 }
@@ -135,16 +527,13 @@ fn visible_project() -> Vec<Vec<(Principal, u64, Group)>> {
     })
 }
 #[update]
-async fn add_group(account: Principal, group: Group) -> Result<(), String> {
+async fn add_group(account: Principal, group: Group) -> Result<(), ManageError> {
     let caller = ic_cdk::api::caller();
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => user.add_group(group.clone(), caller),
-        },
-    )?;
+    with_users(|users| {
+        users
+            .user_mut(account, group.id)?
+            .add_group(group.clone(), caller)
+    })?;
     log!(
         &account.to_string(),
         group.id,
@@ -157,16 +546,19 @@ async fn add_group(account: Principal, group: Group) -> Result<(), String> {
 }
 
 #[update]
-async fn remove_group(account: Principal, group_id: u64) -> Result<(), String> {
+async fn remove_group(account: Principal, group_id: u64) -> Result<(), ManageError> {
     let caller = ic_cdk::api::caller();
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => user.remove_group(group_id, caller),
-        },
-    )?;
+    with_users(|users| {
+        if let Some(group) = users
+            .user_mut(account, group_id)?
+            .remove_group(group_id, caller)?
+        {
+            users.on_commit(move || {
+                MEMBERSHIP_INDEX.with(|index| index.borrow_mut().remove_group(account, &group))
+            });
+        }
+        Ok(())
+    })?;
     log!(
         &account.to_string(),
         group_id,
@@ -178,806 +570,4506 @@ async fn remove_group(account: Principal, group_id: u64) -> Result<(), String> {
     Ok(())
 }
 
-#[query]
-fn get_user_info(account: Principal) -> Result<User, String> {
-    let caller = ic_cdk::api::caller();
-    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
-        None => {
-            return Err("user does not exist".to_string());
-        }
-        Some(user) => user.get_user_info(caller),
-    })
-}
-
+// The caller offers one of its groups to another user, a new offer replaces the previous one
 #[update]
-async fn add_project(account: Principal, group_id: u64, project: Project) -> Result<(), String> {
+async fn offer_group_transfer(group_id: u64, to: Principal) -> Result<GroupTransfer, ManageError> {
     let caller = ic_cdk::api::caller();
-    let members =
-        USER_STORAGE.with(
-            |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-                None => {
-                    return Err("user does not exist".to_string());
-                }
-                Some(user) => user.add_project(group_id, project.clone(), caller),
-            },
-        )?;
-
     USER_STORAGE.with(|user_storage| {
-        for i in members {
-            match user_storage.borrow_mut().get_mut(&i) {
-                None => {
-                    return Err("user does not exist".to_string());
+        let user_storage = user_storage.borrow();
+        match user_storage.get(&caller) {
+            None => {
+                return Err(ManageError::not_found(ResourceKind::User, caller));
+            }
+            Some(user) => {
+                if !user.groups.contains_key(&group_id) {
+                    return Err(ManageError::not_found(ResourceKind::Group, group_id));
                 }
-                Some(user) => user.add_project_relation(caller, group_id, project.id),
-            };
+            }
+        }
+        if to == caller {
+            return Err(ManageError::InvalidArgument(
+                "the group already belongs to the caller".to_string(),
+            ));
+        }
+        if !user_storage.contains_key(&to) {
+            return Err(ManageError::not_found(ResourceKind::User, to));
         }
         Ok(())
     })?;
+    let transfer = GroupTransfer::new(caller, group_id, to, ic_cdk::api::time());
+    TRANSFER_STORAGE.with(|transfer_storage| {
+        transfer_storage
+            .borrow_mut()
+            .insert((caller, group_id), transfer.clone());
+    });
     log!(
-        &account.to_string(),
+        &caller.to_string(),
         group_id,
         &caller.to_string(),
-        Action::UpdateProject(group_id, project.id, "add_project".to_string()),
-        &project
+        Action::UpdateGroup(group_id, "offer_group_transfer".to_string()),
+        &to.to_string()
     )()
     .await;
-    Ok(())
+    Ok(transfer)
 }
 
+// Called by the owner to withdraw the offer or by the recipient to decline it
 #[update]
-async fn remove_project(account: Principal, group_id: u64, project_id: u64) -> Result<(), String> {
+async fn cancel_group_transfer(from: Principal, group_id: u64) -> Result<(), ManageError> {
     let caller = ic_cdk::api::caller();
-    let members =
-        USER_STORAGE.with(
-            |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-                None => {
-                    return Err("user does not exist".to_string());
-                }
-                Some(user) => user.remove_project(group_id, project_id, caller),
-            },
-        )?;
-
-    USER_STORAGE.with(|user_storage| {
-        for i in members {
-            match user_storage.borrow_mut().get_mut(&i) {
-                None => {
-                    return Err("user does not exist".to_string());
-                }
-                Some(user) => user.remove_project_relation(caller, project_id),
-            };
+    TRANSFER_STORAGE.with(|transfer_storage| {
+        let mut transfer_storage = transfer_storage.borrow_mut();
+        match transfer_storage.get(&(from, group_id)) {
+            Some(transfer) if transfer.from == caller || transfer.to == caller => {
+                transfer_storage.remove(&(from, group_id));
+                Ok(())
+            }
+            _ => Err(ManageError::not_found(ResourceKind::Transfer, group_id)),
         }
-        Ok(())
     })?;
-
     log!(
-        &account.to_string(),
+        &from.to_string(),
         group_id,
         &caller.to_string(),
-        Action::UpdateProject(group_id, project_id, "remove_project".to_string()),
-        &project_id
+        Action::UpdateGroup(group_id, "cancel_group_transfer".to_string()),
+        &from.to_string()
     )()
     .await;
     Ok(())
 }
 
+// Moves the group with all its projects under the caller, together with everything
+// keyed by the previous owner: memberships, cycles, modules, uploads, rollouts,
+// deletions, logs and images
 #[update]
-async fn add_group_member(account: Principal, group_id: u64, member: Member) -> Result<(), String> {
+async fn accept_group_transfer(from: Principal, group_id: u64) -> Result<(), ManageError> {
     let caller = ic_cdk::api::caller();
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => user.add_group_member(group_id, member.clone(), caller),
-        },
-    )?;
-
-    USER_STORAGE.with(|user_storage| {
-        match user_storage.borrow_mut().get_mut(&member.identity) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => user.add_group_relation(account, group_id),
-        }
-    })?;
+    transfer_group(from, group_id, caller)?;
 
+    let (log_canister, image_store_canister) =
+        unsafe { (constant::LOG_CANISTER, constant::IMAGE_STORE_CANISTER) };
+    let logs: ic_cdk::api::call::CallResult<()> =
+        ic_cdk::api::call::call(log_canister, "move_logs", (from, group_id, caller)).await;
+    let images: ic_cdk::api::call::CallResult<(Result<(), String>,)> = ic_cdk::api::call::call(
+        image_store_canister,
+        "move_images",
+        (from, group_id, caller),
+    )
+    .await;
     log!(
-        &account.to_string(),
+        &caller.to_string(),
         group_id,
         &caller.to_string(),
-        Action::UpdateGroup(group_id, "add_group_member".to_string()),
-        &member
+        Action::UpdateGroup(group_id, "accept_group_transfer".to_string()),
+        &from.to_string(),
+        &logs,
+        &images
     )()
     .await;
     Ok(())
 }
 
-#[update]
-async fn remove_group_member(
-    account: Principal,
-    group_id: u64,
-    member: Principal,
-) -> Result<(), String> {
-    let caller = ic_cdk::api::caller();
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => user.remove_group_member(group_id, member, caller),
-        },
-    )?;
+// Moves the group offered by from under to, once to accepted the offer
+fn transfer_group(from: Principal, group_id: u64, to: Principal) -> Result<(), ManageError> {
+    match TRANSFER_STORAGE
+        .with(|transfer_storage| transfer_storage.borrow().get(&(from, group_id)).cloned())
+    {
+        Some(transfer) if transfer.to == to => {}
+        _ => return Err(ManageError::not_found(ResourceKind::Transfer, group_id)),
+    }
+    with_users(|users| {
+        if users.group(to, group_id)?.is_some() {
+            return Err(ManageError::already_exists(ResourceKind::Group, group_id));
+        }
+        let mut group = match users.user_mut(from, group_id)?.groups.remove(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group,
+        };
+        group.owner = Some(to);
+        users
+            .user_mut(to, group_id)?
+            .groups
+            .insert(group_id, group.clone());
+        users.on_commit(move || {
+            MEMBERSHIP_INDEX.with(|index| index.borrow_mut().move_group(from, to, &group));
+            move_group_records(from, to, group_id);
+        });
+        Ok(())
+    })
+}
 
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&member) {
-            None => {
-                return Err("user does not exist".to_string());
+// Moves the records kept for a group and its projects from one owner to another
+fn move_group_records(from: Principal, to: Principal, group_id: u64) {
+    TRANSFER_STORAGE.with(|transfer_storage| {
+        transfer_storage.borrow_mut().remove(&(from, group_id));
+    });
+    CYCLE_STORAGE.with(|cycle_storage| {
+        let mut cycle_storage = cycle_storage.borrow_mut();
+        let keys: Vec<(Principal, u64, u64)> = cycle_storage
+            .keys()
+            .filter(|(account, group, _)| *account == from && *group == group_id)
+            .cloned()
+            .collect();
+        for key in keys {
+            let cycle = cycle_storage.remove(&key).unwrap();
+            cycle_storage.insert((to, key.1, key.2), cycle);
+        }
+    });
+    MONITOR_STORAGE.with(|monitor_storage| {
+        let mut monitor_storage = monitor_storage.borrow_mut();
+        let keys: Vec<Monitor_Key> = monitor_storage
+            .keys()
+            .filter(|(account, group, _, _)| *account == from && *group == group_id)
+            .cloned()
+            .collect();
+        for key in keys {
+            let history = monitor_storage.remove(&key).unwrap();
+            monitor_storage.insert((to, key.1, key.2, key.3), history);
+        }
+    });
+    REGISTRY_STORAGE.with(|registry_storage| {
+        let mut registry_storage = registry_storage.borrow_mut();
+        let keys: Vec<(Principal, u64, u64)> = registry_storage
+            .keys()
+            .filter(|(account, group, _)| *account == from && *group == group_id)
+            .cloned()
+            .collect();
+        for key in keys {
+            let registry = registry_storage.remove(&key).unwrap();
+            registry_storage.insert((to, key.1, key.2), registry);
+        }
+    });
+    UPLOAD_STORAGE.with(|upload_storage| {
+        for upload in upload_storage.borrow_mut().values_mut() {
+            if upload.account == from && upload.group_id == group_id {
+                upload.account = to;
             }
-            Some(user) => user.remove_group_relation(account, group_id),
-        },
-    )?;
-    log!(
-        &account.to_string(),
-        group_id,
-        &caller.to_string(),
-        Action::UpdateGroup(group_id, "remove_group_member".to_string()),
-        &member
-    )()
-    .await;
-    Ok(())
+        }
+    });
+    ROLLOUT_STORAGE.with(|rollout_storage| {
+        for rollout in rollout_storage.borrow_mut().values_mut() {
+            if rollout.account == from && rollout.group_id == group_id {
+                rollout.account = to;
+            }
+        }
+    });
+    DELETION_STORAGE.with(|deletion_storage| {
+        for request in deletion_storage.borrow_mut().values_mut() {
+            if request.account == from && request.group_id == group_id {
+                request.account = to;
+            }
+        }
+    });
+    // Pending invitations stay valid, acceptance still checks their inviter
+    INVITATION_STORAGE.with(|invitation_storage| {
+        for invitation in invitation_storage.borrow_mut().values_mut() {
+            if invitation.account == from && invitation.group_id == group_id {
+                invitation.account = to;
+            }
+        }
+    });
+    NOTICE_STORAGE.with(|notice_storage| {
+        let mut notice_storage = notice_storage.borrow_mut();
+        let keys: Vec<Notice_Key> = notice_storage
+            .keys()
+            .filter(|(account, group, _, _)| *account == from && *group == group_id)
+            .cloned()
+            .collect();
+        for key in keys {
+            let expiration_time = notice_storage.remove(&key).unwrap();
+            notice_storage.insert((to, key.1, key.2, key.3), expiration_time);
+        }
+    });
+}
+
+// Pending transfers offered by or to the caller
+#[query]
+fn get_group_transfers() -> Vec<GroupTransfer> {
+    let caller = ic_cdk::api::caller();
+    TRANSFER_STORAGE.with(|transfer_storage| {
+        transfer_storage
+            .borrow()
+            .values()
+            .filter(|transfer| transfer.from == caller || transfer.to == caller)
+            .cloned()
+            .collect()
+    })
+}
+
+#[query]
+fn get_user_info(account: Principal) -> Result<User, ManageError> {
+    let caller = ic_cdk::api::caller();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.get_user_info(caller),
+    })
 }
 
 #[update]
-async fn add_project_member(
+async fn add_project(
     account: Principal,
     group_id: u64,
-    project_id: u64,
-    member: Member,
-) -> Result<(), String> {
+    project: Project,
+) -> Result<(), ManageError> {
     let caller = ic_cdk::api::caller();
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => user.add_project_member(group_id, project_id, member.clone(), caller),
-        },
-    )?;
-    USER_STORAGE.with(|user_storage| {
-        match user_storage.borrow_mut().get_mut(&member.identity) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => user.add_project_relation(account, group_id, project_id),
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
         }
+        Some(user) => user.group_identity_check(group_id, Permission::ManageProjects, caller),
+    })?;
+    let mut controllabilities: Vec<Controllability> = Vec::new();
+    for canister in project.canisters.iter() {
+        controllabilities.push(Controllability::check(*canister, caller).await?);
+    }
+    with_user(account, group_id, |user| {
+        user.add_project(group_id, project.clone(), caller)?;
+        for (canister, controllability) in project.canisters.iter().zip(controllabilities) {
+            user.set_controllability(group_id, project.id, *canister, controllability)?;
+        }
+        Ok(())
     })?;
-
     log!(
         &account.to_string(),
         group_id,
         &caller.to_string(),
-        Action::UpdateProject(group_id, project_id, "add_project_member".to_string()),
-        &member
+        Action::UpdateProject(group_id, project.id, "add_project".to_string()),
+        &project
     )()
     .await;
     Ok(())
 }
 
 #[update]
-async fn remove_project_member(
+async fn remove_project(
     account: Principal,
     group_id: u64,
     project_id: u64,
-    member: Principal,
-) -> Result<(), String> {
+) -> Result<(), ManageError> {
     let caller = ic_cdk::api::caller();
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => user.remove_project_member(group_id, project_id, member, caller),
-        },
-    )?;
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&member) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => user.remove_project_relation(account, project_id),
-        },
-    )?;
+    with_users(|users| {
+        let members = users
+            .user_mut(account, group_id)?
+            .remove_project(group_id, project_id, caller)?;
+        for member in members {
+            remove_membership(
+                users,
+                member,
+                Membership::new(account, group_id, Some(project_id)),
+            );
+        }
+        Ok(())
+    })?;
 
     log!(
         &account.to_string(),
         group_id,
         &caller.to_string(),
-        Action::UpdateProject(group_id, project_id, "remove_project_member".to_string()),
-        &group_id,
-        &project_id,
-        &member
+        Action::UpdateProject(group_id, project_id, "remove_project".to_string()),
+        &project_id
     )()
     .await;
     Ok(())
 }
 
 #[update]
-async fn add_project_canister(
+async fn invite_group_member(
     account: Principal,
     group_id: u64,
-    project_id: u64,
-    canister: Principal,
-) -> Result<(), String> {
-    let caller = ic_cdk::caller();
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => user.add_project_canister(group_id, project_id, canister, caller),
-        },
-    )?;
-
+    member: Member,
+) -> Result<Invitation, ManageError> {
+    let caller = ic_cdk::api::caller();
+    let invitation = issue_invitation(account, group_id, None, member, caller)?;
     log!(
         &account.to_string(),
         group_id,
         &caller.to_string(),
-        Action::UpdateProjectCanister(group_id, project_id, "add_project_canister".to_string()),
-        &canister.to_string()
+        Action::UpdateGroup(group_id, "invite_group_member".to_string()),
+        &invitation.member
     )()
     .await;
-    Ok(())
+    Ok(invitation)
 }
 
 #[update]
-async fn remove_project_canister(
+async fn remove_group_member(
     account: Principal,
     group_id: u64,
-    project_id: u64,
-    canister: Principal,
-) -> Result<(), String> {
+    member: Principal,
+) -> Result<(), ManageError> {
     let caller = ic_cdk::api::caller();
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => user.remove_project_canister(group_id, project_id, canister, caller),
-        },
-    )?;
-
+    with_users(|users| {
+        users
+            .user_mut(account, group_id)?
+            .remove_group_member(group_id, member, caller)?;
+        remove_membership(users, member, Membership::new(account, group_id, None));
+        Ok(())
+    })?;
     log!(
         &account.to_string(),
         group_id,
         &caller.to_string(),
-        Action::UpdateProjectCanister(group_id, project_id, "remove_project_canister".to_string()),
-        &canister.to_string()
+        Action::UpdateGroup(group_id, "remove_group_member".to_string()),
+        &member
     )()
     .await;
     Ok(())
 }
 
 #[update]
-async fn update_controller(
-    new_controller: Principal,
-    canister: Principal,
-) -> Result<(), String>{
+async fn invite_project_member(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    member: Member,
+) -> Result<Invitation, ManageError> {
     let caller = ic_cdk::api::caller();
-
-    // log!(new_controller, caller, &canister.to_string())().await;
-
-    let controllers: Option<Vec<Principal>> = Some(vec![caller, new_controller]);
-    let compute_allocation: Nat = "0".parse().unwrap();
-    let memory_allocation: Nat = "0".parse().unwrap();
-    let freezing_threshold: Nat = "2_592_000".parse().unwrap();
-    let canister_settings = CanisterSettings::new(
-        controllers,
-        Some(compute_allocation),
-        Some(memory_allocation),
-        Some(freezing_threshold),
-    );
-    let mange_canister = ManageCanister::new(canister, canister_settings);
-    return mange_canister.set_controller().await;
+    let invitation = issue_invitation(account, group_id, Some(project_id), member, caller)?;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProject(group_id, project_id, "invite_project_member".to_string()),
+        &invitation.member
+    )()
+    .await;
+    Ok(invitation)
 }
 
-#[update]
-async fn delete_canister_directly(
-    canister: Principal,
-) -> Result<(), String>{
-    // let caller = ic_cdk::api::caller();
-    // let controllers: Option<Vec<Principal>> = Some(vec![caller]);
-    // let compute_allocation: Nat = "0".parse().unwrap();
-    // let memory_allocation: Nat = "0".parse().unwrap();
-    // let freezing_threshold: Nat = "2_592_000".parse().unwrap();
-    // let canister_settings = CanisterSettings::new(
-    //     controllers,
-    //     Some(compute_allocation),
-    //     Some(memory_allocation),
-    //     Some(freezing_threshold),
-    // );
-    // let mange_canister = ManageCanister::new(canister, canister_settings);
-
-    return ManageCanister::delete_canister(canister).await;
+fn issue_invitation(
+    account: Principal,
+    group_id: u64,
+    project_id: Option<u64>,
+    member: Member,
+    sender: Principal,
+) -> Result<Invitation, ManageError> {
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.invite_check(group_id, project_id, &member, sender),
+    })?;
+    INVITATION_STORAGE.with(|invitation_storage| {
+        let mut invitation_storage = invitation_storage.borrow_mut();
+        if invitation_storage.values().any(|invitation| {
+            invitation.is_for(account, group_id, project_id)
+                && invitation.invitee() == member.identity
+        }) {
+            return Err(ManageError::already_exists(
+                ResourceKind::Invitation,
+                member.identity,
+            ));
+        }
+        let invitation_id = unsafe {
+            INVITATION_ID += 1;
+            INVITATION_ID
+        };
+        let invitation = Invitation::new(
+            invitation_id,
+            account,
+            group_id,
+            project_id,
+            sender,
+            ic_cdk::api::time(),
+            member,
+        );
+        invitation_storage.insert(invitation_id, invitation.clone());
+        Ok(invitation)
+    })
 }
 
+// Only acceptance adds the member and its membership, on behalf of the inviter who must
+// still be allowed to add members
 #[update]
-pub async fn update_project_git_repo_url(
-    account: Principal,
-    group_id: u64,
-    project_id: u64,
-    git: String,
-) -> Result<(), String> {
+async fn accept_invitation(invitation_id: u64) -> Result<(), ManageError> {
     let caller = ic_cdk::api::caller();
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-            None => {
-                return Err("user does not exist".to_string());
+    let invitation = match INVITATION_STORAGE
+        .with(|invitation_storage| invitation_storage.borrow().get(&invitation_id).cloned())
+    {
+        Some(invitation) if invitation.invitee() == caller => invitation,
+        _ => {
+            return Err(ManageError::not_found(
+                ResourceKind::Invitation,
+                invitation_id,
+            ))
+        }
+    };
+    let account = invitation.account;
+    let group_id = invitation.group_id;
+    let mut member = invitation.member.clone();
+    member.join_time = ic_cdk::api::time();
+    with_users(|users| {
+        let user = users.user_mut(account, group_id)?;
+        match invitation.project_id {
+            None => user.add_group_member(group_id, member, invitation.inviter)?,
+            Some(project_id) => {
+                user.add_project_member(group_id, project_id, member, invitation.inviter)?
             }
-            Some(user) => user.update_project_git_repo_url(group_id, project_id, &git, caller),
-        },
-    )?;
+        }
+        insert_membership(
+            users,
+            caller,
+            Membership::new(account, group_id, invitation.project_id),
+        );
+        users.on_commit(move || {
+            INVITATION_STORAGE.with(|invitation_storage| {
+                invitation_storage.borrow_mut().remove(&invitation_id);
+            })
+        });
+        Ok(())
+    })?;
     log!(
         &account.to_string(),
         group_id,
         &caller.to_string(),
-        Action::UpdateProject(
-            group_id,
-            project_id,
-            "update_project_git_repo_url".to_string()
-        ),
-        git
+        Action::UpdateGroup(group_id, "accept_invitation".to_string()),
+        &invitation
     )()
     .await;
     Ok(())
 }
 
+// Declined by the invitee or withdrawn by a member allowed to add members
 #[update]
-pub async fn update_canister_cycle_floor(
-    account: Principal,
-    group_id: u64,
-    project_id: u64,
-    floor: Nat,
-) -> Result<(), String> {
+async fn cancel_invitation(invitation_id: u64) -> Result<(), ManageError> {
     let caller = ic_cdk::api::caller();
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => {
-                user.update_canister_cycle_floor(group_id, project_id, floor.clone(), caller)
-            }
-        },
-    )?;
+    let invitation = INVITATION_STORAGE
+        .with(|invitation_storage| invitation_storage.borrow().get(&invitation_id).cloned())
+        .ok_or(ManageError::not_found(
+            ResourceKind::Invitation,
+            invitation_id,
+        ))?;
+    if invitation.invitee() != caller {
+        USER_STORAGE.with(
+            |user_storage| match user_storage.borrow().get(&invitation.account) {
+                None => {
+                    return Err(ManageError::not_found(
+                        ResourceKind::User,
+                        invitation.account,
+                    ));
+                }
+                Some(user) => match invitation.project_id {
+                    None => user.group_identity_check(
+                        invitation.group_id,
+                        Permission::ManageMembers,
+                        caller,
+                    ),
+                    Some(project_id) => user.project_identity_check(
+                        invitation.group_id,
+                        project_id,
+                        Permission::ManageMembers,
+                        caller,
+                    ),
+                },
+            },
+        )?;
+    }
+    INVITATION_STORAGE.with(|invitation_storage| {
+        invitation_storage.borrow_mut().remove(&invitation_id);
+    });
     log!(
-        &account.to_string(),
-        group_id,
+        &invitation.account.to_string(),
+        invitation.group_id,
         &caller.to_string(),
-        Action::UpdateProject(
-            group_id,
-            project_id,
-            "update_canister_cycle_floor".to_string()
-        ),
-        floor.to_string()
+        Action::UpdateGroup(invitation.group_id, "cancel_invitation".to_string()),
+        &invitation
     )()
     .await;
     Ok(())
 }
 
+// Groups and projects the caller is an active member of, with its effective authority there
+#[query]
+fn get_my_memberships() -> Vec<MembershipInfo> {
+    let caller = ic_cdk::api::caller();
+    let now = ic_cdk::api::time();
+    let memberships = MEMBERSHIP_INDEX.with(|index| index.borrow().get(caller));
+    USER_STORAGE.with(|user_storage| {
+        let user_storage = user_storage.borrow();
+        memberships
+            .into_iter()
+            .filter_map(|membership| {
+                user_storage
+                    .get(&membership.owner)?
+                    .groups
+                    .get(&membership.group_id)?
+                    .membership_info(membership.owner, membership.project_id, caller, now)
+            })
+            .collect()
+    })
+}
+
+// Pending invitations of the caller
+#[query]
+fn get_my_invitations() -> Vec<Invitation> {
+    let caller = ic_cdk::api::caller();
+    INVITATION_STORAGE.with(|invitation_storage| {
+        invitation_storage
+            .borrow()
+            .values()
+            .filter(|invitation| invitation.invitee() == caller)
+            .cloned()
+            .collect()
+    })
+}
+
+// Pending invitations to the group and its projects
+#[query]
+fn get_group_invitations(
+    account: Principal,
+    group_id: u64,
+) -> Result<Vec<Invitation>, ManageError> {
+    let caller = ic_cdk::api::caller();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.group_identity_check(group_id, Permission::ManageMembers, caller),
+    })?;
+    Ok(INVITATION_STORAGE.with(|invitation_storage| {
+        invitation_storage
+            .borrow()
+            .values()
+            .filter(|invitation| invitation.account == account && invitation.group_id == group_id)
+            .cloned()
+            .collect()
+    }))
+}
+
 #[update]
-pub async fn update_project_visibility(
+async fn remove_project_member(
     account: Principal,
     group_id: u64,
     project_id: u64,
-    visibility: Profile,
-) -> Result<(), String> {
+    member: Principal,
+) -> Result<(), ManageError> {
     let caller = ic_cdk::api::caller();
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => {
-                user.update_project_visibility(group_id, project_id, visibility.clone(), caller)
-            }
-        },
-    )?;
+    with_users(|users| {
+        users
+            .user_mut(account, group_id)?
+            .remove_project_member(group_id, project_id, member, caller)?;
+        remove_membership(
+            users,
+            member,
+            Membership::new(account, group_id, Some(project_id)),
+        );
+        Ok(())
+    })?;
 
     log!(
         &account.to_string(),
         group_id,
         &caller.to_string(),
-        Action::UpdateProject(
-            group_id,
-            project_id,
-            "update_project_visibility".to_string()
-        ),
-        &visibility
+        Action::UpdateProject(group_id, project_id, "remove_project_member".to_string()),
+        &group_id,
+        &project_id,
+        &member
     )()
     .await;
     Ok(())
 }
 
 #[update]
-pub async fn update_project_description(
+async fn add_project_canister(
     account: Principal,
     group_id: u64,
     project_id: u64,
-    description: String,
-) -> Result<(), String> {
-    let caller = ic_cdk::api::caller();
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => {
-                user.update_project_description(group_id, project_id, &description, caller)
-            }
-        },
-    )?;
+    canister: Principal,
+) -> Result<Controllability, ManageError> {
+    let caller = ic_cdk::caller();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => {
+            user.project_identity_check(group_id, project_id, Permission::ManageCanisters, caller)
+        }
+    })?;
+    let controllability = Controllability::check(canister, caller).await?;
+    with_user(account, group_id, |user| {
+        user.add_project_canister(group_id, project_id, canister, caller)?;
+        user.set_controllability(group_id, project_id, canister, controllability.clone())
+    })?;
+
     log!(
         &account.to_string(),
         group_id,
         &caller.to_string(),
-        Action::UpdateProject(
-            group_id,
-            project_id,
-            "update_project_description".to_string()
-        ),
-        description
+        Action::UpdateProjectCanister(group_id, project_id, "add_project_canister".to_string()),
+        &canister.to_string(),
+        &controllability
     )()
     .await;
-    Ok(())
+    Ok(controllability)
 }
 
 #[update]
-pub async fn update_group_member_authority(
+async fn remove_project_canister(
     account: Principal,
     group_id: u64,
-    member: Principal,
-    auth: Authority,
-) -> Result<(), String> {
+    project_id: u64,
+    canister: Principal,
+) -> Result<(), ManageError> {
     let caller = ic_cdk::api::caller();
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => {
-                user.update_group_member_authority(group_id, member, auth.clone(), caller)
-            }
-        },
-    )?;
+    with_user(account, group_id, |user| {
+        user.remove_project_canister(group_id, project_id, canister, caller)
+    })?;
+
     log!(
         &account.to_string(),
         group_id,
         &caller.to_string(),
-        Action::UpdateGroup(group_id, "update_group_member_authority".to_string()),
-        &member.to_string(),
-        auth
+        Action::UpdateProjectCanister(group_id, project_id, "remove_project_canister".to_string()),
+        &canister.to_string()
     )()
     .await;
     Ok(())
 }
 
+// Records the controllers of a project canister after a change made by the manage canister
+fn record_controllers(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    canister: Principal,
+    controllers: Vec<Principal>,
+    caller: Principal,
+) {
+    let _ = with_user(account, group_id, |user| {
+        let registrant = user
+            .get_project(group_id, project_id)
+            .ok()
+            .and_then(|project| project.controllability.get(&canister))
+            .map_or(caller, |controllability| controllability.registrant);
+        let controllability = Controllability::new(controllers, registrant, ic_cdk::api::time());
+        user.set_controllability(group_id, project_id, canister, controllability)
+    });
+}
+
+// Replaces the controllers of a project canister with the caller and new_controller,
+// the manage canister always stays a controller
 #[update]
-pub async fn update_project_member_authority(
+async fn update_controller(
     account: Principal,
     group_id: u64,
     project_id: u64,
-    member: Principal,
-    auth: Authority,
-) -> Result<(), String> {
+    canister: Principal,
+    new_controller: Principal,
+) -> Result<(), ManageError> {
     let caller = ic_cdk::api::caller();
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => user.update_project_member_authority(
-                group_id,
-                project_id,
-                member,
-                auth.clone(),
-                caller,
-            ),
-        },
-    )?;
+    authority_check(canister, account, caller).await;
+    let mut controllers = vec![ic_cdk::api::id(), caller];
+    if !controllers.contains(&new_controller) {
+        controllers.push(new_controller);
+    }
+    let settings = CanisterSettings::new(Some(controllers.clone()), None, None, None);
+    let task = USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => {
+            user.update_project_canister_settings(group_id, project_id, canister, settings, caller)
+        }
+    })?;
+    let result = futures::join!(task).0;
+    if result.is_ok() {
+        record_controllers(
+            account,
+            group_id,
+            project_id,
+            canister,
+            controllers.clone(),
+            caller,
+        );
+    }
     log!(
         &account.to_string(),
         group_id,
         &caller.to_string(),
-        Action::UpdateProject(
-            group_id,
-            project_id,
-            "update_project_member_authority".to_string()
-        ),
-        &member.to_string(),
-        auth
+        Action::UpdateProjectCanister(group_id, project_id, "update_controller".to_string()),
+        &canister.to_string(),
+        &controllers,
+        &result
     )()
     .await;
-    Ok(())
+    result
 }
 
-#[update]
-async fn update_group_basic_information(
+// Add or remove one controller of a project canister, keeping the other controllers
+async fn change_canister_controller(
     account: Principal,
     group_id: u64,
-    name: String,
-    description: String,
-    visibility: Profile,
-    url: String,
-) -> Result<(), String> {
+    project_id: u64,
+    canister: Principal,
+    controller: Principal,
+    add: bool,
+    method: &str,
+) -> Result<Vec<Principal>, ManageError> {
     let caller = ic_cdk::api::caller();
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => user.update_group_basic_information(
-                group_id,
-                name.clone(),
-                description.clone(),
-                visibility,
-                url,
-                caller,
-            ),
-        },
-    )?;
+    authority_check(canister, account, caller).await;
+    let (status, _) = ManageCanister::get_canister_status(canister, Nat::default()).await?;
+    let before = status.settings.controllers.unwrap_or_default();
+    let after = util::change_controllers(before.clone(), controller, add, ic_cdk::api::id())?;
+    let settings = CanisterSettings::new(Some(after.clone()), None, None, None);
+    let task = USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => {
+            user.update_project_canister_settings(group_id, project_id, canister, settings, caller)
+        }
+    })?;
+    let result = futures::join!(task).0;
+    if result.is_ok() {
+        record_controllers(
+            account,
+            group_id,
+            project_id,
+            canister,
+            after.clone(),
+            caller,
+        );
+    }
     log!(
         &account.to_string(),
         group_id,
         &caller.to_string(),
-        Action::UpdateGroup(group_id, "update_group_info".to_string()),
-        &name,
-        &description
+        Action::UpdateProjectCanister(group_id, project_id, method.to_string()),
+        &canister.to_string(),
+        &before,
+        &after,
+        &result
     )()
     .await;
-    Ok(())
+    result?;
+    Ok(after)
 }
 
 #[update]
-async fn update_project_basic_information(
+pub async fn add_canister_controller(
     account: Principal,
     group_id: u64,
     project_id: u64,
-    name: String,
-    description: String,
-    visibility: Profile,
-    git: String,
-    canister_cycle_floor: Nat,
-    canisters: Vec<Principal>,
-) -> Result<(), String> {
-    let caller = ic_cdk::api::caller();
-    USER_STORAGE.with(
-        |user_storage| match user_storage.borrow_mut().get_mut(&account) {
-            None => {
-                return Err("user does not exist".to_string());
-            }
-            Some(user) => user.update_project_basic_information(
-                group_id,
-                project_id,
-                name.clone(),
-                description.clone(),
-                visibility,
-                git,
-                canister_cycle_floor,
-                &canisters,
-                caller,
-            ),
-        },
-    )?;
-    log!(
-        &account.to_string(),
+    canister: Principal,
+    controller: Principal,
+) -> Result<Vec<Principal>, ManageError> {
+    change_canister_controller(
+        account,
         group_id,
-        &caller.to_string(),
-        Action::UpdateProject(group_id, project_id, "update_project_info".to_string()),
-        &name,
-        &description
-    )()
-    .await;
-    Ok(())
+        project_id,
+        canister,
+        controller,
+        true,
+        "add_canister_controller",
+    )
+    .await
 }
 
 #[update]
-pub async fn start_project_canister(
+pub async fn remove_canister_controller(
     account: Principal,
     group_id: u64,
     project_id: u64,
     canister: Principal,
-) -> Result<(), String> {
-    let caller = ic_cdk::api::caller();
-    authority_check(canister, account, caller).await;
-    let task = USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
-        None => {
-            return Err("user does not exist".to_string());
-        }
-        Some(user) => user.start_project_canister(group_id, project_id, canister, caller),
-    })?;
-    futures::join!(task);
-    log!(
-        &account.to_string(),
+    controller: Principal,
+) -> Result<Vec<Principal>, ManageError> {
+    change_canister_controller(
+        account,
         group_id,
-        &caller.to_string(),
-        Action::UpdateProjectCanister(group_id, project_id, "start_project_canister".to_string()),
-        &canister.to_string()
-    )()
-    .await;
-    Ok(())
+        project_id,
+        canister,
+        controller,
+        false,
+        "remove_canister_controller",
+    )
+    .await
 }
 
+// Kept for existing callers, the canister is deleted through the deletion workflow:
+// it needs a deletion request past its cooldown, see request_canister_deletion
 #[update]
-pub async fn stop_project_canister(
+async fn delete_canister_directly(
     account: Principal,
     group_id: u64,
     project_id: u64,
     canister: Principal,
-) -> Result<(), String> {
+) -> Result<(), ManageError> {
+    delete_project_canister(account, group_id, project_id, canister).await
+}
+
+#[update]
+pub async fn update_project_git_repo_url(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    git: String,
+) -> Result<(), ManageError> {
     let caller = ic_cdk::api::caller();
-    authority_check(canister, account, caller).await;
-    let task = USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
-        None => {
-            return Err("user does not exist".to_string());
-        }
-        Some(user) => user.stop_project_canister(group_id, project_id, canister, caller),
+    with_user(account, group_id, |user| {
+        user.update_project_git_repo_url(group_id, project_id, &git, caller)
     })?;
-    futures::join!(task);
     log!(
         &account.to_string(),
         group_id,
         &caller.to_string(),
-        Action::UpdateProjectCanister(group_id, project_id, "stop_project_canister".to_string()),
-        &canister.to_string()
+        Action::UpdateProject(
+            group_id,
+            project_id,
+            "update_project_git_repo_url".to_string()
+        ),
+        git
     )()
     .await;
     Ok(())
 }
 
 #[update]
-pub async fn delete_project_canister(
+pub async fn update_canister_cycle_floor(
     account: Principal,
     group_id: u64,
     project_id: u64,
-    canister: Principal,
-) -> Result<(), String> {
+    floor: Nat,
+) -> Result<(), ManageError> {
     let caller = ic_cdk::api::caller();
-    authority_check(canister, account, caller).await;
-    let task = USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
-        None => {
-            return Err("user does not exist".to_string());
-        }
-        Some(user) => user.delete_project_canister(group_id, project_id, canister, caller),
+    with_user(account, group_id, |user| {
+        user.update_canister_cycle_floor(group_id, project_id, floor.clone(), caller)
     })?;
-    futures::join!(task);
     log!(
         &account.to_string(),
         group_id,
         &caller.to_string(),
-        Action::UpdateProjectCanister(group_id, project_id, "delete_project_canister".to_string()),
-        &canister.to_string()
+        Action::UpdateProject(
+            group_id,
+            project_id,
+            "update_canister_cycle_floor".to_string()
+        ),
+        floor.to_string()
     )()
     .await;
     Ok(())
 }
 
 #[update]
-pub async fn install_code(
+pub async fn update_project_top_up_policy(
     account: Principal,
     group_id: u64,
     project_id: u64,
-    canister: Principal,
-    install_mod: InstallCodeMode,
-    wasm: Vec<u8>,
-    args: Vec<u8>,
-) -> Result<(), String> {
+    policy: Option<TopUpPolicy>,
+) -> Result<(), ManageError> {
     let caller = ic_cdk::api::caller();
-    authority_check(canister, account, caller).await;
-    let task = USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
         None => {
-            return Err("user does not exist".to_string());
+            return Err(ManageError::not_found(ResourceKind::User, account));
         }
-        Some(user) => user.install_code(
-            group_id,
-            project_id,
-            canister,
-            install_mod,
-            wasm,
-            args,
-            caller,
-        ),
+        Some(user) => user.project_identity_check(group_id, project_id, Permission::TopUp, caller),
     })?;
-    futures::join!(task);
+    CYCLE_STORAGE.with(|cycle_storage| {
+        cycle_storage
+            .borrow_mut()
+            .entry((account, group_id, project_id))
+            .or_default()
+            .policy = policy.clone();
+    });
     log!(
         &account.to_string(),
         group_id,
         &caller.to_string(),
-        Action::UpdateProjectCanister(group_id, project_id, "install_code".to_string()),
-        &canister.to_string()
+        Action::UpdateProject(
+            group_id,
+            project_id,
+            "update_project_top_up_policy".to_string()
+        ),
+        &policy
     )()
     .await;
     Ok(())
 }
 
-#[query]
-pub fn get_project_info(
+// Cycles attached to the call are added to the project's top-up budget
+#[update]
+pub fn deposit_project_cycle_budget(
     account: Principal,
     group_id: u64,
     project_id: u64,
-) -> Result<Option<Project>, String> {
-    let caller = ic_cdk::api::caller();
+) -> Result<Nat, ManageError> {
     USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
         None => {
-            return Err("user does not exist".to_string());
+            return Err(ManageError::not_found(ResourceKind::User, account));
         }
-        Some(user) => user.get_project_info(group_id, project_id, caller),
+        Some(user) => user.get_project(group_id, project_id).map(|_| ()),
+    })?;
+    let available = ic_cdk::api::call::msg_cycles_available128();
+    let accepted = ic_cdk::api::call::msg_cycles_accept128(available);
+    CYCLE_STORAGE.with(|cycle_storage| {
+        let mut cycle_storage = cycle_storage.borrow_mut();
+        let project_cycle = cycle_storage
+            .entry((account, group_id, project_id))
+            .or_default();
+        project_cycle.deposit(Nat::from(accepted));
+        Ok(project_cycle.budget.clone())
     })
 }
 
 #[query]
-pub fn get_group_info(account: Principal, group_id: u64) -> Result<Option<Group>, String> {
+pub fn get_project_cycle(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+) -> Result<Option<ProjectCycle>, ManageError> {
     let caller = ic_cdk::api::caller();
     USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
         None => {
-            return Err("user does not exist".to_string());
+            return Err(ManageError::not_found(ResourceKind::User, account));
         }
-        Some(user) => user.get_group_info(group_id, caller),
+        Some(user) => {
+            user.project_identity_check(group_id, project_id, Permission::ViewStatus, caller)
+        }
+    })?;
+    Ok(CYCLE_STORAGE.with(|cycle_storage| {
+        cycle_storage
+            .borrow()
+            .get(&(account, group_id, project_id))
+            .cloned()
+    }))
+}
+
+// (account, group_id, project_id, canister, canister_cycle_floor) of every registered project canister
+fn project_canisters() -> Vec<(Principal, u64, u64, Principal, Nat)> {
+    USER_STORAGE.with(|user_storage| {
+        let mut canisters = Vec::new();
+        for (account, user) in user_storage.borrow().iter() {
+            for (group_id, group) in user.groups.iter() {
+                for (project_id, project) in group.projects.iter() {
+                    for canister in project.canisters.iter() {
+                        canisters.push((
+                            *account,
+                            *group_id,
+                            *project_id,
+                            *canister,
+                            project.canister_cycle_floor.clone(),
+                        ));
+                    }
+                }
+            }
+        }
+        canisters
     })
 }
 
-#[query]
-pub fn get_group_member_info(
+async fn top_up_canister(
     account: Principal,
     group_id: u64,
-    member: Principal,
-) -> Result<Member, String> {
-    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
-        None => {
-            return Err("user does not exist".to_string());
+    project_id: u64,
+    canister: Principal,
+    floor: Nat,
+) {
+    let cycles = match ManageCanister::get_canister_status(canister, floor.clone()).await {
+        Err(_) => return,
+        Ok((status, _)) => status.cycles,
+    };
+    if cycles >= floor {
+        return;
+    }
+    let key = (account, group_id, project_id);
+    let reserved =
+        CYCLE_STORAGE.with(
+            |cycle_storage| match cycle_storage.borrow_mut().get_mut(&key) {
+                None => Err(ManageError::not_found(
+                    ResourceKind::TopUpPolicy,
+                    project_id,
+                )),
+                Some(project_cycle) => {
+                    let amount = project_cycle.reserve(ic_cdk::api::time(), available_cycles())?;
+                    let source = project_cycle.policy.as_ref().unwrap().source.clone();
+                    Ok((amount, source))
+                }
+            },
+        );
+    let (amount, source) = match reserved {
+        Err(_) => return,
+        Ok(reserved) => reserved,
+    };
+
+    let in_flight = match source {
+        TopUpSource::ManageCanister => util::nat_to_u128(&amount),
+        TopUpSource::ProjectBudget => 0,
+    };
+    unsafe {
+        IN_FLIGHT_CYCLES += in_flight;
+    }
+    let result = ManageCanister::deposit_cycles(canister, util::nat_to_u128(&amount)).await;
+    unsafe {
+        IN_FLIGHT_CYCLES -= in_flight;
+    }
+    CYCLE_STORAGE.with(|cycle_storage| {
+        if let Some(project_cycle) = cycle_storage.borrow_mut().get_mut(&key) {
+            match result {
+                Ok(()) => project_cycle.add_record(TopUpRecord {
+                    canister: canister,
+                    amount: amount.clone(),
+                    cycles_before: cycles.clone(),
+                    time: ic_cdk::api::time(),
+                }),
+                Err(_) => project_cycle.release(amount.clone(), source),
+            }
         }
-        Some(user) => user.get_group_member_info(group_id, member),
-    })
+    });
+    log!(
+        &account.to_string(),
+        group_id,
+        &ic_cdk::api::id().to_string(),
+        Action::UpdateProjectCanister(group_id, project_id, "top_up_canister".to_string()),
+        &canister.to_string(),
+        amount.to_string(),
+        cycles.to_string(),
+        &result
+    )()
+    .await;
 }
 
-#[pre_upgrade]
-fn pre_upgrade() {
+async fn top_up_project_canisters() {
+    let canisters: Vec<(Principal, u64, u64, Principal, Nat)> =
+        CYCLE_STORAGE.with(|cycle_storage| {
+            let cycle_storage = cycle_storage.borrow();
+            project_canisters()
+                .into_iter()
+                .filter(|(account, group_id, project_id, _, _)| {
+                    match cycle_storage.get(&(*account, *group_id, *project_id)) {
+                        None => false,
+                        Some(project_cycle) => project_cycle.policy.is_some(),
+                    }
+                })
+                .collect()
+        });
+    for (account, group_id, project_id, canister, floor) in canisters {
+        top_up_canister(account, group_id, project_id, canister, floor).await;
+    }
     unsafe {
-        USER_STORAGE.with(|user_storage| {
-            let data_storage: Vec<(Principal, User)> = user_storage
-                .borrow()
-                .iter()
-                .map(|(k, v)| (k.clone(), v.clone()))
-                .collect();
-            ic_cdk::storage::stable_save((OWNER, constant::LOG_CANISTER, data_storage))
-                .expect("stable_save failed");
-        })
+        TOP_UP_RUNNING = false;
     }
 }
 
-#[post_upgrade]
-fn post_upgrade() {
+#[heartbeat]
+fn heartbeat() {
+    let now = ic_cdk::api::time();
     unsafe {
-        let data_storage: (Principal, Principal, Vec<(Principal, User)>) =
-            ic_cdk::storage::stable_restore().expect("data recovery failed");
-        OWNER = data_storage.0;
-        constant::LOG_CANISTER = data_storage.1;
-        let data_storage: User_Storage = data_storage.2.into_iter().collect();
-        USER_STORAGE.with(|user_storage| {
-            *user_storage.borrow_mut() = data_storage;
-        });
+        if !TOP_UP_RUNNING && now >= LAST_TOP_UP + constant::TOP_UP_INTERVAL {
+            TOP_UP_RUNNING = true;
+            LAST_TOP_UP = now;
+            ic_cdk::spawn(top_up_project_canisters());
+        }
+        if !MONITOR_RUNNING && now >= LAST_MONITOR + constant::MONITOR_INTERVAL {
+            MONITOR_RUNNING = true;
+            LAST_MONITOR = now;
+            ic_cdk::spawn(monitor_project_canisters());
+        }
+        if now >= LAST_MEMBER_SWEEP + constant::MEMBER_SWEEP_INTERVAL {
+            LAST_MEMBER_SWEEP = now;
+            ic_cdk::spawn(sweep_expired_members(now));
+            UPLOAD_STORAGE.with(|upload_storage| {
+                upload_storage
+                    .borrow_mut()
+                    .retain(|_, session| !session.is_expired(now));
+            });
+        }
     }
 }
 
-#[cfg(test)]
-mod test_util {
-    use super::*;
-    #[test]
-    fn test_is_controller() {
-        let err = String::from("get_canister_status faile: 5: Only the controllers of the canister r7inp-6aaaa-aaaaa-aaabq-cai can control it.\nCanister\'s controllers: rrkah-fqaaa-aaaaa-aaaaq-cai dzhx6-f63tz-aslp6-xxyzd-pknwt-lxpho-q2wsx-pvwwd-v3nq6-75ek5-rqe\nSender\'s ID: r7inp-6aaaa-aaaaa-aaabq-cai");
-        let controller = Principal::from_text("r7inp-6aaaa-aaaaa-aaabq-cai").unwrap();
-        assert_eq!(util::is_controller(err.clone(), controller), false);
+// Removes expired members from every group and project together with their memberships,
+// each removal is logged with the manage canister as operator
+async fn sweep_expired_members(now: u64) {
+    let removed = with_users(|users| {
+        let mut removed: Vec<(Principal, u64, Option<u64>, Principal)> = Vec::new();
+        for (account, group_id) in users.group_keys() {
+            // Only the groups with expired members are staged
+            match users.group(account, group_id)? {
+                Some(group) if group.has_expired_members(now) => {}
+                _ => continue,
+            }
+            for (project_id, member) in users
+                .group_mut(account, group_id)?
+                .remove_expired_members(now)
+            {
+                remove_membership(
+                    users,
+                    member,
+                    Membership::new(account, group_id, project_id),
+                );
+                removed.push((account, group_id, project_id, member));
+            }
+        }
+        Ok(removed)
+    })
+    .unwrap_or_default();
+    let operator = ic_cdk::api::id();
+    for (account, group_id, project_id, member) in removed {
+        let action = match project_id {
+            None => Action::UpdateGroup(group_id, "remove_expired_member".to_string()),
+            Some(project_id) => {
+                Action::UpdateProject(group_id, project_id, "remove_expired_member".to_string())
+            }
+        };
+        log!(
+            &account.to_string(),
+            group_id,
+            &operator.to_string(),
+            action,
+            &member.to_string()
+        )()
+        .await;
+    }
+    notify_expiring_members(now).await;
+}
 
-        let controller = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
-        assert_eq!(util::is_controller(err, controller), true);
+// Every active member of every group and project whose expiration falls within window from now
+fn expiring_members(now: u64, window: u64) -> Vec<ExpiringMember> {
+    USER_STORAGE.with(|user_storage| {
+        let mut expiring = Vec::new();
+        for (account, user) in user_storage.borrow().iter() {
+            for (group_id, group) in user.groups.iter() {
+                for (project_id, member) in group.expiring_members(now, window) {
+                    expiring.push(ExpiringMember::new(*account, *group_id, project_id, member));
+                }
+            }
+        }
+        expiring
+    })
+}
+
+// Sends one notification per member and expiration time to the notification canister,
+// a renewed member is notified again before its new expiration. Failed calls are retried
+// on the next sweep
+async fn notify_expiring_members(now: u64) {
+    let notification_canister = match unsafe { constant::NOTIFICATION_CANISTER } {
+        None => return,
+        Some(notification_canister) => notification_canister,
+    };
+    let expiring = expiring_members(now, unsafe { constant::EXPIRY_NOTICE });
+    let pending = NOTICE_STORAGE
+        .with(|notice_storage| pending_notices(&mut notice_storage.borrow_mut(), expiring, now));
+    for (key, expiring_member) in pending {
+        let res: ic_cdk::api::call::CallResult<()> = ic_cdk::api::call::call(
+            notification_canister,
+            "notify_member_expiry",
+            (&expiring_member,),
+        )
+        .await;
+        if res.is_err() {
+            NOTICE_STORAGE.with(|notice_storage| {
+                notice_storage.borrow_mut().remove(&key);
+            });
+        }
+    }
+}
+
+// The expiring members not notified about their current expiration time yet, they are marked
+// as notified. Notices of past expirations are dropped
+fn pending_notices(
+    notice_storage: &mut Notice_Storage,
+    expiring: Vec<ExpiringMember>,
+    now: u64,
+) -> Vec<(Notice_Key, ExpiringMember)> {
+    notice_storage.retain(|_, expiration_time| *expiration_time >= now);
+    let mut pending = Vec::new();
+    for expiring_member in expiring {
+        let expiration_time = match expiring_member.member.expiration_time {
+            None => continue,
+            Some(expiration_time) => expiration_time,
+        };
+        let key = (
+            expiring_member.account,
+            expiring_member.group_id,
+            expiring_member.project_id,
+            expiring_member.member.identity,
+        );
+        if notice_storage.insert(key, expiration_time) != Some(expiration_time) {
+            pending.push((key, expiring_member));
+        }
+    }
+    pending
+}
+
+// Members of the caller's groups and their projects expiring within window, in nanoseconds
+#[query]
+fn get_expiring_members(window: u64) -> Result<Vec<ExpiringMember>, ManageError> {
+    let caller = ic_cdk::api::caller();
+    let now = ic_cdk::api::time();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&caller) {
+        None => Err(ManageError::not_found(ResourceKind::User, caller)),
+        Some(user) => {
+            let mut expiring = Vec::new();
+            for (group_id, group) in user.groups.iter() {
+                for (project_id, member) in group.expiring_members(now, window) {
+                    expiring.push(ExpiringMember::new(caller, *group_id, project_id, member));
+                }
+            }
+            Ok(expiring)
+        }
+    })
+}
+
+fn expiration_check(expiration_time: Option<u64>) -> Result<(), ManageError> {
+    if let Some(expiration_time) = expiration_time {
+        if expiration_time <= ic_cdk::api::time() {
+            return Err(ManageError::InvalidArgument(
+                "expiration time is in the past".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// expiration_time None removes the expiration of the member
+#[update]
+pub async fn update_group_member_expiration(
+    account: Principal,
+    group_id: u64,
+    member: Principal,
+    expiration_time: Option<u64>,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    expiration_check(expiration_time)?;
+    with_user(account, group_id, |user| {
+        user.update_group_member_expiration(group_id, member, expiration_time, caller)
+    })?;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateGroup(group_id, "update_group_member_expiration".to_string()),
+        &member.to_string(),
+        expiration_time
+    )()
+    .await;
+    Ok(())
+}
+
+// expiration_time None removes the expiration of the member
+#[update]
+pub async fn update_project_member_expiration(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    member: Principal,
+    expiration_time: Option<u64>,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    expiration_check(expiration_time)?;
+    with_user(account, group_id, |user| {
+        user.update_project_member_expiration(group_id, project_id, member, expiration_time, caller)
+    })?;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProject(
+            group_id,
+            project_id,
+            "update_project_member_expiration".to_string()
+        ),
+        &member.to_string(),
+        expiration_time
+    )()
+    .await;
+    Ok(())
+}
+
+#[update]
+pub async fn update_project_visibility(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    visibility: Profile,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    with_user(account, group_id, |user| {
+        user.update_project_visibility(group_id, project_id, visibility.clone(), caller)
+    })?;
+
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProject(
+            group_id,
+            project_id,
+            "update_project_visibility".to_string()
+        ),
+        &visibility
+    )()
+    .await;
+    Ok(())
+}
+
+#[update]
+pub async fn update_project_description(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    description: String,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    with_user(account, group_id, |user| {
+        user.update_project_description(group_id, project_id, &description, caller)
+    })?;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProject(
+            group_id,
+            project_id,
+            "update_project_description".to_string()
+        ),
+        description
+    )()
+    .await;
+    Ok(())
+}
+
+#[update]
+pub async fn update_group_member_authority(
+    account: Principal,
+    group_id: u64,
+    member: Principal,
+    auth: Authority,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    with_user(account, group_id, |user| {
+        user.update_group_member_authority(group_id, member, auth.clone(), caller)
+    })?;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateGroup(group_id, "update_group_member_authority".to_string()),
+        &member.to_string(),
+        auth
+    )()
+    .await;
+    Ok(())
+}
+
+#[update]
+pub async fn update_project_member_authority(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    member: Principal,
+    auth: Authority,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    with_user(account, group_id, |user| {
+        user.update_project_member_authority(group_id, project_id, member, auth.clone(), caller)
+    })?;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProject(
+            group_id,
+            project_id,
+            "update_project_member_authority".to_string()
+        ),
+        &member.to_string(),
+        auth
+    )()
+    .await;
+    Ok(())
+}
+
+#[update]
+pub async fn update_group_member_role(
+    account: Principal,
+    group_id: u64,
+    member: Principal,
+    role: Option<String>,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    with_user(account, group_id, |user| {
+        user.update_group_member_role(group_id, member, role.clone(), caller)
+    })?;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateGroup(group_id, "update_group_member_role".to_string()),
+        &member.to_string(),
+        &role
+    )()
+    .await;
+    Ok(())
+}
+
+#[update]
+pub async fn update_project_member_role(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    member: Principal,
+    role: Option<String>,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    with_user(account, group_id, |user| {
+        user.update_project_member_role(group_id, project_id, member, role.clone(), caller)
+    })?;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProject(
+            group_id,
+            project_id,
+            "update_project_member_role".to_string()
+        ),
+        &member.to_string(),
+        &role
+    )()
+    .await;
+    Ok(())
+}
+
+// permissions None removes the entry of the principal from the canister acl
+#[update]
+pub async fn update_canister_acl(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    canister: Principal,
+    principal: Principal,
+    permissions: Option<Vec<Permission>>,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    with_user(account, group_id, |user| {
+        user.update_canister_acl(
+            group_id,
+            project_id,
+            canister,
+            principal,
+            permissions.clone(),
+            caller,
+        )
+    })?;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProjectCanister(group_id, project_id, "update_canister_acl".to_string()),
+        &canister.to_string(),
+        &principal.to_string(),
+        &permissions
+    )()
+    .await;
+    Ok(())
+}
+
+#[update]
+pub async fn create_group_role(
+    account: Principal,
+    group_id: u64,
+    role: Role,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    with_user(account, group_id, |user| {
+        user.create_group_role(group_id, role.clone(), caller)
+    })?;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateGroup(group_id, "create_group_role".to_string()),
+        &role
+    )()
+    .await;
+    Ok(())
+}
+
+#[update]
+pub async fn update_group_role(
+    account: Principal,
+    group_id: u64,
+    role: Role,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    with_user(account, group_id, |user| {
+        user.update_group_role(group_id, role.clone(), caller)
+    })?;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateGroup(group_id, "update_group_role".to_string()),
+        &role
+    )()
+    .await;
+    Ok(())
+}
+
+#[update]
+pub async fn delete_group_role(
+    account: Principal,
+    group_id: u64,
+    name: String,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    with_user(account, group_id, |user| {
+        user.delete_group_role(group_id, &name, caller)
+    })?;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateGroup(group_id, "delete_group_role".to_string()),
+        &name
+    )()
+    .await;
+    Ok(())
+}
+
+#[query]
+pub fn get_group_roles(account: Principal, group_id: u64) -> Result<Vec<Role>, ManageError> {
+    let caller = ic_cdk::api::caller();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.get_group_roles(group_id, caller),
+    })
+}
+
+#[update]
+async fn update_group_basic_information(
+    account: Principal,
+    group_id: u64,
+    name: String,
+    description: String,
+    visibility: Profile,
+    url: String,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    with_user(account, group_id, |user| {
+        user.update_group_basic_information(
+            group_id,
+            name.clone(),
+            description.clone(),
+            visibility,
+            url,
+            caller,
+        )
+    })?;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateGroup(group_id, "update_group_info".to_string()),
+        &name,
+        &description
+    )()
+    .await;
+    Ok(())
+}
+
+#[update]
+async fn update_project_basic_information(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    name: String,
+    description: String,
+    visibility: Profile,
+    git: String,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    with_user(account, group_id, |user| {
+        user.update_project_basic_information(
+            group_id,
+            project_id,
+            name.clone(),
+            description.clone(),
+            visibility,
+            git,
+            caller,
+        )
+    })?;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProject(group_id, project_id, "update_project_info".to_string()),
+        &name,
+        &description
+    )()
+    .await;
+    Ok(())
+}
+
+#[update]
+pub async fn start_project_canister(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    canister: Principal,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    authority_check(canister, account, caller).await;
+    let task = USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.start_project_canister(group_id, project_id, canister, caller),
+    })?;
+    let result = futures::join!(task).0;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProjectCanister(group_id, project_id, "start_project_canister".to_string()),
+        &canister.to_string(),
+        &result
+    )()
+    .await;
+    result
+}
+
+#[update]
+pub async fn update_project_canister_settings(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    canister: Principal,
+    settings: CanisterSettings,
+) -> Result<CanisterSettings, ManageError> {
+    let caller = ic_cdk::api::caller();
+    authority_check(canister, account, caller).await;
+    let task = USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.update_project_canister_settings(
+            group_id,
+            project_id,
+            canister,
+            settings.clone(),
+            caller,
+        ),
+    })?;
+    let (before, _) = ManageCanister::get_canister_status(canister, Nat::default()).await?;
+    let result = futures::join!(task).0;
+    // The update is logged even when its settings can not be read back
+    let after = match &result {
+        Ok(()) => Some(
+            ManageCanister::get_canister_status(canister, Nat::default())
+                .await
+                .map(|(status, _)| status.settings),
+        ),
+        Err(_) => None,
+    };
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProjectCanister(
+            group_id,
+            project_id,
+            "update_project_canister_settings".to_string()
+        ),
+        &canister.to_string(),
+        &before.settings,
+        &after,
+        &result
+    )()
+    .await;
+    result?;
+    after.unwrap_or(Ok(before.settings))
+}
+
+#[update]
+pub async fn stop_project_canister(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    canister: Principal,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    authority_check(canister, account, caller).await;
+    let task = USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.stop_project_canister(group_id, project_id, canister, caller),
+    })?;
+    let result = futures::join!(task).0;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProjectCanister(group_id, project_id, "stop_project_canister".to_string()),
+        &canister.to_string(),
+        &result
+    )()
+    .await;
+    result
+}
+
+fn get_deletion(canister: Principal) -> Result<DeletionRequest, ManageError> {
+    DELETION_STORAGE.with(|deletion_storage| {
+        deletion_storage
+            .borrow()
+            .get(&canister)
+            .cloned()
+            .ok_or(ManageError::not_found(ResourceKind::Deletion, canister))
+    })
+}
+
+fn deletion_identity_check(
+    canister: Principal,
+    sender: Principal,
+) -> Result<DeletionRequest, ManageError> {
+    let request = get_deletion(canister)?;
+    USER_STORAGE.with(
+        |user_storage| match user_storage.borrow().get(&request.account) {
+            None => {
+                return Err(ManageError::not_found(ResourceKind::User, request.account));
+            }
+            Some(user) => user.canister_identity_check(
+                request.group_id,
+                request.project_id,
+                canister,
+                Permission::Delete,
+                sender,
+            ),
+        },
+    )?;
+    Ok(request)
+}
+
+#[update]
+pub async fn request_canister_deletion(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    canister: Principal,
+    withdrawal: Option<CyclesWithdrawal>,
+) -> Result<DeletionRequest, ManageError> {
+    let caller = ic_cdk::api::caller();
+    authority_check(canister, account, caller).await;
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => {
+            user.canister_identity_check(
+                group_id,
+                project_id,
+                canister,
+                Permission::Delete,
+                caller,
+            )?;
+            if !user
+                .get_project(group_id, project_id)?
+                .canisters
+                .contains(&canister)
+            {
+                return Err(ManageError::not_found(ResourceKind::Canister, canister));
+            }
+            Ok(())
+        }
+    })?;
+    if let Some(withdrawal) = &withdrawal {
+        let exists = REGISTRY_STORAGE.with(|registry_storage| {
+            match registry_storage
+                .borrow()
+                .get(&(account, group_id, project_id))
+            {
+                None => false,
+                Some(registry) => registry
+                    .get(&withdrawal.module_name, &withdrawal.module_version)
+                    .is_some(),
+            }
+        });
+        if !exists {
+            return Err(ManageError::not_found(
+                ResourceKind::Module,
+                format!("{}@{}", withdrawal.module_name, withdrawal.module_version),
+            ));
+        }
+    }
+    let request = DeletionRequest::new(
+        canister,
+        account,
+        group_id,
+        project_id,
+        caller,
+        ic_cdk::api::time(),
+        withdrawal,
+    );
+    DELETION_STORAGE.with(|deletion_storage| {
+        let mut deletion_storage = deletion_storage.borrow_mut();
+        if deletion_storage.contains_key(&canister) {
+            return Err(ManageError::already_exists(
+                ResourceKind::Deletion,
+                canister,
+            ));
+        }
+        deletion_storage.insert(canister, request.clone());
+        Ok(())
+    })?;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProjectCanister(
+            group_id,
+            project_id,
+            "request_canister_deletion".to_string()
+        ),
+        &canister.to_string(),
+        &request.withdrawal
+    )()
+    .await;
+    Ok(request)
+}
+
+#[update]
+pub async fn cancel_canister_deletion(canister: Principal) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    let request = deletion_identity_check(canister, caller)?;
+    DELETION_STORAGE.with(|deletion_storage| {
+        let mut deletion_storage = deletion_storage.borrow_mut();
+        if let Some(request) = deletion_storage.get(&canister) {
+            if request.status != DeletionStatus::Pending {
+                return Err(ManageError::InvalidState(
+                    "deletion is already in progress".to_string(),
+                ));
+            }
+        }
+        deletion_storage.remove(&canister);
+        Ok(())
+    })?;
+    log!(
+        &request.account.to_string(),
+        request.group_id,
+        &caller.to_string(),
+        Action::UpdateProjectCanister(
+            request.group_id,
+            request.project_id,
+            "cancel_canister_deletion".to_string()
+        ),
+        &canister.to_string()
+    )()
+    .await;
+    Ok(())
+}
+
+#[query]
+pub fn get_project_deletions(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+) -> Result<Vec<DeletionRequest>, ManageError> {
+    let caller = ic_cdk::api::caller();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => {
+            user.project_identity_check(group_id, project_id, Permission::ViewStatus, caller)
+        }
+    })?;
+    Ok(DELETION_STORAGE.with(|deletion_storage| {
+        deletion_storage
+            .borrow()
+            .values()
+            .filter(|request| {
+                request.account == account
+                    && request.group_id == group_id
+                    && request.project_id == project_id
+            })
+            .cloned()
+            .collect()
+    }))
+}
+
+async fn wait_stopped(canister: Principal) -> Result<(), ManageError> {
+    for _ in 0..constant::STOP_POLL_LIMIT {
+        let (status, _) = ManageCanister::get_canister_status(canister, Nat::default()).await?;
+        if let CanisterStatus::Stopped = status.status {
+            return Ok(());
+        }
+    }
+    Err(ManageError::InvalidState(
+        "canister did not stop".to_string(),
+    ))
+}
+
+async fn stop_and_wait(request: &DeletionRequest, caller: Principal) -> Result<(), ManageError> {
+    let task =
+        USER_STORAGE.with(
+            |user_storage| match user_storage.borrow().get(&request.account) {
+                None => {
+                    return Err(ManageError::not_found(ResourceKind::User, request.account));
+                }
+                Some(user) => user.stop_project_canister(
+                    request.group_id,
+                    request.project_id,
+                    request.canister,
+                    caller,
+                ),
+            },
+        )?;
+    futures::join!(task).0?;
+    wait_stopped(request.canister).await
+}
+
+// Reinstall the stopped canister with the drain module and let it send its cycles to the target
+async fn withdraw_canister_cycles(
+    request: &DeletionRequest,
+    withdrawal: &CyclesWithdrawal,
+    caller: Principal,
+) -> Result<(), ManageError> {
+    install_registry_module(
+        request.account,
+        request.group_id,
+        request.project_id,
+        request.canister,
+        ModuleInstall::new(
+            &withdrawal.module_name,
+            &withdrawal.module_version,
+            InstallCodeMode::Reinstall,
+            Vec::new(),
+        ),
+        caller,
+    )
+    .await?;
+    let task =
+        USER_STORAGE.with(
+            |user_storage| match user_storage.borrow().get(&request.account) {
+                None => {
+                    return Err(ManageError::not_found(ResourceKind::User, request.account));
+                }
+                Some(user) => user.start_project_canister(
+                    request.group_id,
+                    request.project_id,
+                    request.canister,
+                    caller,
+                ),
+            },
+        )?;
+    futures::join!(task).0?;
+    let result: Result<(), _> =
+        ic_cdk::api::call::call(request.canister, "withdraw_cycles", (withdrawal.target,)).await;
+    result
+        .map_err(|(code, msg)| ManageError::management_call_failed("withdraw_cycles", code, msg))?;
+    stop_and_wait(request, caller).await
+}
+
+// Stop, drain and delete the canister, then drop it from the project and the canister records
+async fn run_canister_deletion(
+    request: &DeletionRequest,
+    caller: Principal,
+) -> Result<(), ManageError> {
+    stop_and_wait(request, caller).await?;
+    if let Some(withdrawal) = &request.withdrawal {
+        withdraw_canister_cycles(request, withdrawal, caller).await?;
+    }
+    let task =
+        USER_STORAGE.with(
+            |user_storage| match user_storage.borrow().get(&request.account) {
+                None => {
+                    return Err(ManageError::not_found(ResourceKind::User, request.account));
+                }
+                Some(user) => user.delete_project_canister(
+                    request.group_id,
+                    request.project_id,
+                    request.canister,
+                    caller,
+                ),
+            },
+        )?;
+    futures::join!(task).0?;
+    // The deleter may hold Delete without ManageCanisters, the canister is gone either way
+    let unregistered = with_user(request.account, request.group_id, |user| {
+        user.unregister_project_canister(request.group_id, request.project_id, request.canister)
+    });
+    MONITOR_STORAGE.with(|monitor_storage| {
+        monitor_storage.borrow_mut().remove(&(
+            request.account,
+            request.group_id,
+            request.project_id,
+            request.canister,
+        ))
+    });
+    INSTALL_STORAGE.with(|install_storage| install_storage.borrow_mut().remove(&request.canister));
+    collect_wasms();
+    unregistered
+}
+
+// Confirm a deletion requested through request_canister_deletion once its cooldown is over
+#[update]
+pub async fn delete_project_canister(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    canister: Principal,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    authority_check(canister, account, caller).await;
+    let request = deletion_identity_check(canister, caller)?;
+    if request.account != account
+        || request.group_id != group_id
+        || request.project_id != project_id
+    {
+        return Err(ManageError::not_found(ResourceKind::Deletion, canister));
+    }
+    DELETION_STORAGE.with(|deletion_storage| {
+        match deletion_storage.borrow_mut().get_mut(&canister) {
+            None => Err(ManageError::not_found(ResourceKind::Deletion, canister)),
+            Some(request) => request.confirm(ic_cdk::api::time()),
+        }
+    })?;
+    let result = run_canister_deletion(&request, caller).await;
+    DELETION_STORAGE.with(|deletion_storage| {
+        let mut deletion_storage = deletion_storage.borrow_mut();
+        match &result {
+            Ok(()) => {
+                deletion_storage.remove(&canister);
+            }
+            Err(err) => {
+                if let Some(request) = deletion_storage.get_mut(&canister) {
+                    request.fail(err.clone());
+                }
+            }
+        }
+    });
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProjectCanister(group_id, project_id, "delete_project_canister".to_string()),
+        &canister.to_string(),
+        &request.withdrawal,
+        &result
+    )()
+    .await;
+    result
+}
+
+#[update]
+pub async fn install_code(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    canister: Principal,
+    install_mod: InstallCodeMode,
+    wasm: Vec<u8>,
+    args: Vec<u8>,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    authority_check(canister, account, caller).await;
+    let task = USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.install_code(
+            group_id,
+            project_id,
+            canister,
+            install_mod,
+            wasm.clone(),
+            args.clone(),
+            caller,
+        ),
+    })?;
+    let result = futures::join!(task).0;
+    if result.is_ok() {
+        record_install(
+            account,
+            group_id,
+            project_id,
+            canister,
+            InstallRecord::new(&wasm, args, caller, ic_cdk::api::time(), None),
+            wasm,
+        );
+    }
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProjectCanister(group_id, project_id, "install_code".to_string()),
+        &canister.to_string(),
+        &result
+    )()
+    .await;
+    result
+}
+
+#[update]
+fn begin_wasm_upload(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+) -> Result<u64, ManageError> {
+    let caller = ic_cdk::api::caller();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => {
+            user.project_identity_check(group_id, project_id, Permission::InstallCode, caller)
+        }
+    })?;
+    let now = ic_cdk::api::time();
+    UPLOAD_STORAGE.with(|upload_storage| {
+        let mut upload_storage = upload_storage.borrow_mut();
+        upload_storage.retain(|_, session| !session.is_expired(now));
+        if upload_storage
+            .values()
+            .filter(|session| session.belongs_to(account, group_id, project_id))
+            .count()
+            >= constant::UPLOAD_LIMIT
+        {
+            return Err(ManageError::InvalidState(format!(
+                "project already has {} uploads, delete one first",
+                constant::UPLOAD_LIMIT
+            )));
+        }
+        let upload_id = unsafe {
+            UPLOAD_ID += 1;
+            UPLOAD_ID
+        };
+        upload_storage.insert(
+            upload_id,
+            UploadSession::new(upload_id, caller, account, group_id, project_id, now),
+        );
+        Ok(upload_id)
+    })
+}
+
+#[update]
+fn append_wasm_chunk(upload_id: u64, chunk: Vec<u8>) -> Result<u64, ManageError> {
+    let caller = ic_cdk::api::caller();
+    UPLOAD_STORAGE.with(
+        |upload_storage| match upload_storage.borrow_mut().get_mut(&upload_id) {
+            None => Err(ManageError::not_found(ResourceKind::Upload, upload_id)),
+            Some(session) => session.append_chunk(&chunk, caller),
+        },
+    )
+}
+
+#[update]
+fn finalize_wasm_upload(upload_id: u64, sha256: Vec<u8>) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    UPLOAD_STORAGE.with(
+        |upload_storage| match upload_storage.borrow_mut().get_mut(&upload_id) {
+            None => Err(ManageError::not_found(ResourceKind::Upload, upload_id)),
+            Some(session) => session.finalize(sha256, caller),
+        },
+    )
+}
+
+#[update]
+fn delete_wasm_upload(upload_id: u64) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    let (uploader, account, group_id, project_id) = UPLOAD_STORAGE.with(|upload_storage| {
+        upload_storage
+            .borrow()
+            .get(&upload_id)
+            .map(|session| {
+                (
+                    session.uploader,
+                    session.account,
+                    session.group_id,
+                    session.project_id,
+                )
+            })
+            .ok_or(ManageError::not_found(ResourceKind::Upload, upload_id))
+    })?;
+    if uploader != caller {
+        USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+            None => {
+                return Err(ManageError::not_found(ResourceKind::User, account));
+            }
+            Some(user) => {
+                user.project_identity_check(group_id, project_id, Permission::InstallCode, caller)
+            }
+        })?;
+    }
+    UPLOAD_STORAGE.with(|upload_storage| upload_storage.borrow_mut().remove(&upload_id));
+    Ok(())
+}
+
+#[query]
+fn get_wasm_uploads(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+) -> Result<Vec<UploadInfo>, ManageError> {
+    let caller = ic_cdk::api::caller();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => {
+            user.project_identity_check(group_id, project_id, Permission::ViewStatus, caller)
+        }
+    })?;
+    Ok(UPLOAD_STORAGE.with(|upload_storage| {
+        upload_storage
+            .borrow()
+            .values()
+            .filter(|session| session.belongs_to(account, group_id, project_id))
+            .map(|session| session.info())
+            .collect()
+    }))
+}
+
+#[update]
+pub async fn install_code_from_upload(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    canister: Principal,
+    install_mod: InstallCodeMode,
+    upload_id: u64,
+    args: Vec<u8>,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    authority_check(canister, account, caller).await;
+    let wasm =
+        UPLOAD_STORAGE.with(
+            |upload_storage| match upload_storage.borrow().get(&upload_id) {
+                None => Err(ManageError::not_found(ResourceKind::Upload, upload_id)),
+                Some(session) => session
+                    .module(account, group_id, project_id)
+                    .map(|wasm| wasm.to_vec()),
+            },
+        )?;
+    let task = USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.install_code(
+            group_id,
+            project_id,
+            canister,
+            install_mod,
+            wasm.clone(),
+            args.clone(),
+            caller,
+        ),
+    })?;
+    let result = futures::join!(task).0;
+    if result.is_ok() {
+        record_install(
+            account,
+            group_id,
+            project_id,
+            canister,
+            InstallRecord::new(&wasm, args, caller, ic_cdk::api::time(), None),
+            wasm,
+        );
+    }
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProjectCanister(group_id, project_id, "install_code_from_upload".to_string()),
+        &canister.to_string(),
+        upload_id,
+        &result
+    )()
+    .await;
+    result
+}
+
+// Create a canister paid from the manage canister balance or the project budget, controlled by
+// the manage canister and the given project members, and register it in the project
+#[update]
+pub async fn create_project_canister(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    cycles: Nat,
+    source: TopUpSource,
+    controllers: Vec<Principal>,
+    install: Option<(u64, Vec<u8>)>,
+) -> Result<CreatedCanister, ManageError> {
+    let caller = ic_cdk::api::caller();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => {
+            user.project_identity_check(group_id, project_id, Permission::ManageCanisters, caller)?;
+            if install.is_some() {
+                user.project_identity_check(group_id, project_id, Permission::InstallCode, caller)?;
+            }
+            let project = user.get_project(group_id, project_id)?;
+            for controller in controllers.iter() {
+                if *controller != account && !project.members.contains_key(controller) {
+                    return Err(ManageError::not_found(ResourceKind::Member, controller));
+                }
+            }
+            Ok(())
+        }
+    })?;
+    let mut settings_controllers = vec![ic_cdk::api::id()];
+    for controller in controllers {
+        if !settings_controllers.contains(&controller) {
+            settings_controllers.push(controller);
+        }
+    }
+    let settings = CanisterSettings::new(Some(settings_controllers.clone()), None, None, None);
+    settings.validate(ic_cdk::api::id())?;
+    let wasm = match &install {
+        None => None,
+        Some((upload_id, _)) => Some(UPLOAD_STORAGE.with(|upload_storage| {
+            match upload_storage.borrow().get(upload_id) {
+                None => Err(ManageError::not_found(ResourceKind::Upload, upload_id)),
+                Some(session) => session
+                    .module(account, group_id, project_id)
+                    .map(|wasm| wasm.to_vec()),
+            }
+        })?),
+    };
+
+    let key = (account, group_id, project_id);
+    CYCLE_STORAGE.with(|cycle_storage| {
+        let mut cycle_storage = cycle_storage.borrow_mut();
+        let project_cycle = cycle_storage.entry(key).or_default();
+        match source {
+            TopUpSource::ProjectBudget => project_cycle.withdraw(&cycles),
+            TopUpSource::ManageCanister => {
+                project_cycle.draw_allowance(&cycles, available_cycles())
+            }
+        }
+    })?;
+    let in_flight = match source {
+        TopUpSource::ManageCanister => util::nat_to_u128(&cycles),
+        TopUpSource::ProjectBudget => 0,
+    };
+    unsafe {
+        IN_FLIGHT_CYCLES += in_flight;
+    }
+    let result = ManageCanister::create_canister(settings, util::nat_to_u128(&cycles)).await;
+    unsafe {
+        IN_FLIGHT_CYCLES -= in_flight;
+    }
+    if result.is_err() {
+        CYCLE_STORAGE.with(|cycle_storage| {
+            if let Some(project_cycle) = cycle_storage.borrow_mut().get_mut(&key) {
+                project_cycle.refund(cycles.clone(), source.clone());
+            }
+        });
+    }
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProjectCanister(group_id, project_id, "create_project_canister".to_string()),
+        cycles.to_string(),
+        &source,
+        &result
+    )()
+    .await;
+    let canister = result?;
+    // The caller was checked before the cycles were spent, the registration only fails when
+    // the project is gone meanwhile and the new canister is left orphaned
+    let registered = with_user(account, group_id, |user| {
+        user.register_project_canister(group_id, project_id, canister)?;
+        user.set_controllability(
+            group_id,
+            project_id,
+            canister,
+            Controllability::new(settings_controllers, caller, ic_cdk::api::time()),
+        )
+    });
+    if let Err(err) = registered {
+        CYCLE_STORAGE.with(|cycle_storage| {
+            if let Some(project_cycle) = cycle_storage.borrow_mut().get_mut(&key) {
+                project_cycle.refund(cycles.clone(), source.clone());
+            }
+        });
+        log!(
+            &account.to_string(),
+            group_id,
+            &caller.to_string(),
+            Action::UpdateProjectCanister(
+                group_id,
+                project_id,
+                "orphaned_project_canister".to_string()
+            ),
+            &canister.to_string(),
+            cycles.to_string(),
+            &source,
+            &err
+        )()
+        .await;
+        return Err(err);
+    }
+
+    let (wasm, (upload_id, args)) = match (wasm, install) {
+        (Some(wasm), Some(install)) => (wasm, install),
+        _ => {
+            return Ok(CreatedCanister {
+                canister: canister,
+                install_error: None,
+            })
+        }
+    };
+    let task = USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.install_code(
+            group_id,
+            project_id,
+            canister,
+            InstallCodeMode::Install,
+            wasm.clone(),
+            args.clone(),
+            caller,
+        ),
+    })?;
+    let result = futures::join!(task).0;
+    if result.is_ok() {
+        record_install(
+            account,
+            group_id,
+            project_id,
+            canister,
+            InstallRecord::new(&wasm, args, caller, ic_cdk::api::time(), None),
+            wasm,
+        );
+    }
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProjectCanister(group_id, project_id, "install_code_from_upload".to_string()),
+        &canister.to_string(),
+        upload_id,
+        &result
+    )()
+    .await;
+    Ok(CreatedCanister {
+        canister: canister,
+        install_error: result.err(),
+    })
+}
+
+// Keep the installed module for rollbacks and track its registry version on the project,
+// a module installed outside of the registry clears the tracked version
+fn record_install(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    canister: Principal,
+    record: InstallRecord,
+    wasm: Vec<u8>,
+) {
+    let _ = with_user(account, group_id, |user| {
+        user.set_installed_module(group_id, project_id, canister, record.module.clone())
+    });
+    store_wasm(record.sha256.clone(), wasm);
+    INSTALL_STORAGE.with(|install_storage| {
+        let mut install_storage = install_storage.borrow_mut();
+        rollback::push_record(install_storage.entry(canister).or_default(), record);
+    });
+    collect_wasms();
+}
+
+#[query]
+pub fn get_canister_install_history(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    canister: Principal,
+) -> Result<Vec<InstallRecordInfo>, ManageError> {
+    let caller = ic_cdk::api::caller();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.canister_read_check(group_id, project_id, canister, caller),
+    })?;
+    Ok(INSTALL_STORAGE.with(
+        |install_storage| match install_storage.borrow().get(&canister) {
+            None => Vec::new(),
+            Some(history) => history.iter().map(|record| record.info()).collect(),
+        },
+    ))
+}
+
+// Reinstall the module installed before the current one in upgrade mode and
+// return the hash of the reinstalled module
+async fn rollback_canister_module(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    canister: Principal,
+    caller: Principal,
+) -> Result<Vec<u8>, ManageError> {
+    let previous =
+        INSTALL_STORAGE.with(
+            |install_storage| match install_storage.borrow().get(&canister) {
+                None => Err(ManageError::not_found(
+                    ResourceKind::InstallRecord,
+                    canister,
+                )),
+                Some(history) => rollback::previous_record(history).cloned(),
+            },
+        )?;
+    let wasm = load_wasm(&previous.sha256)?;
+    let task = USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.install_code(
+            group_id,
+            project_id,
+            canister,
+            InstallCodeMode::Upgrade,
+            wasm,
+            previous.args.clone(),
+            caller,
+        ),
+    })?;
+    futures::join!(task).0?;
+    let (status, _) = ManageCanister::get_canister_status(canister, Nat::default()).await?;
+    if status.module_hash != Some(previous.sha256.clone()) {
+        return Err(ManageError::HashMismatch {
+            expected: previous.sha256.clone(),
+            actual: status.module_hash,
+        });
+    }
+    // The rolled back module is dropped so that the next rollback goes one module further back
+    INSTALL_STORAGE.with(|install_storage| {
+        if let Some(history) = install_storage.borrow_mut().get_mut(&canister) {
+            history.pop();
+        }
+    });
+    collect_wasms();
+    let _ = with_user(account, group_id, |user| {
+        user.set_installed_module(group_id, project_id, canister, previous.module.clone())
+    });
+    Ok(previous.sha256)
+}
+
+#[update]
+pub async fn rollback_canister(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    canister: Principal,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    authority_check(canister, account, caller).await;
+    let result = rollback_canister_module(account, group_id, project_id, canister, caller).await;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProjectCanister(group_id, project_id, "rollback_canister".to_string()),
+        &canister.to_string(),
+        &result
+    )()
+    .await;
+    result.map(|_| ())
+}
+
+#[update]
+pub async fn publish_wasm_module(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    upload_id: u64,
+    name: String,
+    version: String,
+    release_notes: String,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => {
+            user.project_identity_check(group_id, project_id, Permission::InstallCode, caller)
+        }
+    })?;
+    let key = (account, group_id, project_id);
+    REGISTRY_STORAGE.with(
+        |registry_storage| match registry_storage.borrow().get(&key) {
+            None => Ok(()),
+            Some(registry) => registry.publish_check(&name, &version),
+        },
+    )?;
+    let session = UPLOAD_STORAGE.with(|upload_storage| {
+        let mut upload_storage = upload_storage.borrow_mut();
+        match upload_storage.get(&upload_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Upload, upload_id)),
+            Some(session) => session.module(account, group_id, project_id)?,
+        };
+        Ok(upload_storage.remove(&upload_id).unwrap())
+    })?;
+    let sha256 = session.sha256.unwrap();
+    let module = WasmModule::new(
+        name.clone(),
+        version.clone(),
+        sha256.clone(),
+        caller,
+        release_notes,
+        ic_cdk::api::time(),
+        session.wasm.len() as u64,
+    );
+    REGISTRY_STORAGE.with(|registry_storage| {
+        registry_storage
+            .borrow_mut()
+            .entry(key)
+            .or_default()
+            .publish(module)
+    })?;
+    store_wasm(sha256, session.wasm);
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProject(group_id, project_id, "publish_wasm_module".to_string()),
+        &name,
+        &version
+    )()
+    .await;
+    Ok(())
+}
+
+#[update]
+pub async fn delete_wasm_module(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    name: String,
+    version: String,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => {
+            user.project_identity_check(group_id, project_id, Permission::InstallCode, caller)
+        }
+    })?;
+    REGISTRY_STORAGE.with(|registry_storage| {
+        match registry_storage
+            .borrow_mut()
+            .get_mut(&(account, group_id, project_id))
+        {
+            None => Err(ManageError::not_found(
+                ResourceKind::Module,
+                format!("{}@{}", name, version),
+            )),
+            Some(registry) => registry.remove(&name, &version),
+        }
+    })?;
+    collect_wasms();
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProject(group_id, project_id, "delete_wasm_module".to_string()),
+        &name,
+        &version
+    )()
+    .await;
+    Ok(())
+}
+
+fn store_wasm(sha256: Vec<u8>, wasm: Vec<u8>) {
+    WASM_STORAGE.with(|wasm_storage| {
+        wasm_storage.borrow_mut().entry(sha256).or_insert(wasm);
+    });
+}
+
+fn load_wasm(sha256: &[u8]) -> Result<Vec<u8>, ManageError> {
+    WASM_STORAGE.with(|wasm_storage| match wasm_storage.borrow().get(sha256) {
+        None => Err(ManageError::not_found(
+            ResourceKind::Module,
+            sha256
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>(),
+        )),
+        Some(wasm) => Ok(wasm.clone()),
+    })
+}
+
+// Drops the stored modules no registry version or install record refers to anymore
+fn collect_wasms() {
+    let mut referenced: HashSet<Vec<u8>> = REGISTRY_STORAGE.with(|registry_storage| {
+        registry_storage
+            .borrow()
+            .values()
+            .flat_map(|registry| registry.modules.iter().map(|module| module.sha256.clone()))
+            .collect()
+    });
+    INSTALL_STORAGE.with(|install_storage| {
+        for history in install_storage.borrow().values() {
+            referenced.extend(history.iter().map(|record| record.sha256.clone()));
+        }
+    });
+    WASM_STORAGE.with(|wasm_storage| {
+        wasm_storage
+            .borrow_mut()
+            .retain(|sha256, _| referenced.contains(sha256));
+    });
+}
+
+#[query]
+pub fn get_wasm_modules(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+) -> Result<Vec<ModuleInfo>, ManageError> {
+    let caller = ic_cdk::api::caller();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => {
+            user.project_identity_check(group_id, project_id, Permission::ViewStatus, caller)
+        }
+    })?;
+    Ok(REGISTRY_STORAGE.with(|registry_storage| {
+        match registry_storage
+            .borrow()
+            .get(&(account, group_id, project_id))
+        {
+            None => Vec::new(),
+            Some(registry) => registry
+                .modules
+                .iter()
+                .map(|module| module.info())
+                .collect(),
+        }
+    }))
+}
+
+// Install a registry module version on a project canister and return the hash of the module
+async fn install_registry_module(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    canister: Principal,
+    install: ModuleInstall,
+    caller: Principal,
+) -> Result<Vec<u8>, ManageError> {
+    let installed = REGISTRY_STORAGE.with(|registry_storage| {
+        match registry_storage
+            .borrow()
+            .get(&(account, group_id, project_id))
+            .and_then(|registry| registry.get(&install.name, &install.version))
+        {
+            None => Err(ManageError::not_found(
+                ResourceKind::Module,
+                format!("{}@{}", install.name, install.version),
+            )),
+            Some(module) => Ok(module.installed(ic_cdk::api::time())),
+        }
+    })?;
+    let wasm = load_wasm(&installed.sha256)?;
+    let task = USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.install_code(
+            group_id,
+            project_id,
+            canister,
+            install.mode,
+            wasm.clone(),
+            install.args.clone(),
+            caller,
+        ),
+    })?;
+    futures::join!(task).0?;
+    let sha256 = installed.sha256.clone();
+    record_install(
+        account,
+        group_id,
+        project_id,
+        canister,
+        InstallRecord::new(
+            &wasm,
+            install.args,
+            caller,
+            ic_cdk::api::time(),
+            Some(installed),
+        ),
+        wasm,
+    );
+    Ok(sha256)
+}
+
+// The arguments make up the Candid interface of the endpoint
+#[allow(clippy::too_many_arguments)]
+#[update]
+pub async fn install_wasm_module(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    canister: Principal,
+    install_mod: InstallCodeMode,
+    name: String,
+    version: String,
+    args: Vec<u8>,
+) -> Result<(), ManageError> {
+    let caller = ic_cdk::api::caller();
+    authority_check(canister, account, caller).await;
+    let result = install_registry_module(
+        account,
+        group_id,
+        project_id,
+        canister,
+        ModuleInstall::new(&name, &version, install_mod, args),
+        caller,
+    )
+    .await;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProjectCanister(group_id, project_id, "install_wasm_module".to_string()),
+        &canister.to_string(),
+        &name,
+        &version,
+        &result
+    )()
+    .await;
+    result.map(|_| ())
+}
+
+// Upgrade one canister of a rollout and check that it runs the rollout module afterwards
+async fn rollout_step(rollout: Rollout, canister: Principal, caller: Principal) -> StepStatus {
+    let installed = install_registry_module(
+        rollout.account,
+        rollout.group_id,
+        rollout.project_id,
+        canister,
+        ModuleInstall::new(
+            &rollout.module_name,
+            &rollout.module_version,
+            InstallCodeMode::Upgrade,
+            rollout.args.clone(),
+        ),
+        caller,
+    )
+    .await;
+    let sha256 = match installed {
+        Err(err) => return StepStatus::Failed(err),
+        Ok(sha256) => sha256,
+    };
+    match rollout_check(canister, sha256).await {
+        Ok(()) => StepStatus::Succeeded,
+        Err(err) => StepStatus::CheckFailed(err),
+    }
+}
+
+async fn rollout_check(canister: Principal, sha256: Vec<u8>) -> Result<(), ManageError> {
+    let (status, _) = ManageCanister::get_canister_status(canister, Nat::default()).await?;
+    if let CanisterStatus::Running = status.status {
+    } else {
+        return Err(ManageError::InvalidState(format!(
+            "canister is not running: {:?}",
+            status.status
+        )));
+    }
+    if status.module_hash.as_ref() != Some(&sha256) {
+        return Err(ManageError::HashMismatch {
+            expected: sha256,
+            actual: status.module_hash,
+        });
+    }
+    Ok(())
+}
+
+fn update_rollout<F: FnOnce(&mut Rollout)>(rollout_id: u64, f: F) {
+    ROLLOUT_STORAGE.with(|rollout_storage| {
+        if let Some(rollout) = rollout_storage.borrow_mut().get_mut(&rollout_id) {
+            f(rollout);
+        }
+    });
+}
+
+fn get_rollout(rollout_id: u64) -> Result<Rollout, ManageError> {
+    ROLLOUT_STORAGE.with(|rollout_storage| {
+        rollout_storage
+            .borrow()
+            .get(&rollout_id)
+            .cloned()
+            .ok_or(ManageError::not_found(ResourceKind::Rollout, rollout_id))
+    })
+}
+
+// Upgrade the remaining waves of a rollout and halt at the first wave with a failed canister
+async fn run_rollout(rollout_id: u64, caller: Principal) -> Result<Rollout, ManageError> {
+    loop {
+        let rollout = get_rollout(rollout_id)?;
+        let canisters = match rollout.next_wave() {
+            None => {
+                update_rollout(rollout_id, |rollout| {
+                    rollout.status = RolloutStatus::Completed
+                });
+                return get_rollout(rollout_id);
+            }
+            Some((_, canisters)) => canisters,
+        };
+        // The whole wave is checked before any of its canisters is upgraded
+        let mut checks = Vec::new();
+        for canister in canisters.iter() {
+            checks.push(controller_check(*canister, rollout.account, caller).await);
+        }
+        if checks.iter().any(|check| check.is_err()) {
+            let now = ic_cdk::api::time();
+            update_rollout(rollout_id, |rollout| {
+                for (canister, check) in canisters.iter().zip(checks.into_iter()) {
+                    if let Err(err) = check {
+                        rollout.update_step(*canister, StepStatus::Failed(err), now);
+                    }
+                }
+                rollout.status = RolloutStatus::Halted;
+            });
+            return get_rollout(rollout_id);
+        }
+        let steps = canisters
+            .iter()
+            .map(|canister| rollout_step(rollout.clone(), *canister, caller));
+        let results = futures::future::join_all(steps).await;
+        let now = ic_cdk::api::time();
+        let mut halted = false;
+        update_rollout(rollout_id, |rollout| {
+            for (canister, status) in canisters.iter().zip(results.into_iter()) {
+                if !matches!(status, StepStatus::Succeeded) {
+                    halted = true;
+                }
+                rollout.update_step(*canister, status, now);
+            }
+            if halted {
+                rollout.status = RolloutStatus::Halted;
+            }
+        });
+        if halted {
+            return get_rollout(rollout_id);
+        }
+    }
+}
+
+// Roll every upgraded canister of a rollout back to the module it ran before, last wave first
+async fn run_rollout_rollback(rollout_id: u64, caller: Principal) -> Result<Rollout, ManageError> {
+    let rollout = get_rollout(rollout_id)?;
+    let mut failed = false;
+    for canister in rollout.rollback_canisters() {
+        let rollback = match controller_check(canister, rollout.account, caller).await {
+            Err(err) => Err(err),
+            Ok(()) => {
+                rollback_canister_module(
+                    rollout.account,
+                    rollout.group_id,
+                    rollout.project_id,
+                    canister,
+                    caller,
+                )
+                .await
+            }
+        };
+        let status = match rollback {
+            Ok(_) => StepStatus::RolledBack,
+            Err(err) => {
+                failed = true;
+                StepStatus::RollbackFailed(err)
+            }
+        };
+        let now = ic_cdk::api::time();
+        update_rollout(rollout_id, |rollout| {
+            rollout.update_step(canister, status, now)
+        });
+    }
+    update_rollout(rollout_id, |rollout| {
+        rollout.status = if failed {
+            RolloutStatus::RollbackHalted
+        } else {
+            RolloutStatus::RolledBack
+        };
+    });
+    get_rollout(rollout_id)
+}
+
+fn rollout_identity_check(rollout_id: u64, sender: Principal) -> Result<Rollout, ManageError> {
+    let rollout = get_rollout(rollout_id)?;
+    USER_STORAGE.with(
+        |user_storage| match user_storage.borrow().get(&rollout.account) {
+            None => {
+                return Err(ManageError::not_found(ResourceKind::User, rollout.account));
+            }
+            Some(user) => user.project_identity_check(
+                rollout.group_id,
+                rollout.project_id,
+                Permission::InstallCode,
+                sender,
+            ),
+        },
+    )?;
+    Ok(rollout)
+}
+
+#[update]
+pub async fn start_rollout(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+    name: String,
+    version: String,
+    args: Vec<u8>,
+    waves: Vec<Vec<Principal>>,
+) -> Result<Rollout, ManageError> {
+    let caller = ic_cdk::api::caller();
+    let canisters: Vec<Principal> = waves.iter().flatten().copied().collect();
+    if canisters.is_empty() {
+        return Err(ManageError::InvalidArgument(
+            "rollout has no canisters".to_string(),
+        ));
+    }
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => {
+            user.project_identity_check(group_id, project_id, Permission::InstallCode, caller)?;
+            let project = user.get_project(group_id, project_id)?;
+            for (index, canister) in canisters.iter().enumerate() {
+                if !project.canisters.contains(canister) {
+                    return Err(ManageError::not_found(ResourceKind::Canister, canister));
+                }
+                if canisters[..index].contains(canister) {
+                    return Err(ManageError::InvalidArgument(
+                        "canister appears more than once in the rollout".to_string(),
+                    ));
+                }
+            }
+            Ok(())
+        }
+    })?;
+    let exists = REGISTRY_STORAGE.with(|registry_storage| {
+        match registry_storage
+            .borrow()
+            .get(&(account, group_id, project_id))
+        {
+            None => false,
+            Some(registry) => registry.get(&name, &version).is_some(),
+        }
+    });
+    if !exists {
+        return Err(ManageError::not_found(
+            ResourceKind::Module,
+            format!("{}@{}", name, version),
+        ));
+    }
+    let rollout_id = unsafe {
+        ROLLOUT_ID += 1;
+        ROLLOUT_ID
+    };
+    let rollout = Rollout::new(
+        rollout_id,
+        account,
+        group_id,
+        project_id,
+        caller,
+        ic_cdk::api::time(),
+        name,
+        version,
+        args,
+        waves,
+    );
+    ROLLOUT_STORAGE.with(|rollout_storage| {
+        rollout_storage.borrow_mut().insert(rollout_id, rollout);
+    });
+    let rollout = run_rollout(rollout_id, caller).await?;
+    log!(
+        &account.to_string(),
+        group_id,
+        &caller.to_string(),
+        Action::UpdateProject(group_id, project_id, "start_rollout".to_string()),
+        rollout_id,
+        &rollout.module_name,
+        &rollout.module_version,
+        &rollout.status
+    )()
+    .await;
+    Ok(rollout)
+}
+
+#[update]
+pub async fn resume_rollout(rollout_id: u64) -> Result<Rollout, ManageError> {
+    let caller = ic_cdk::api::caller();
+    let rollout = rollout_identity_check(rollout_id, caller)?;
+    ROLLOUT_STORAGE.with(|rollout_storage| {
+        match rollout_storage.borrow_mut().get_mut(&rollout_id) {
+            None => Err(ManageError::not_found(ResourceKind::Rollout, rollout_id)),
+            Some(rollout) => rollout.resume(),
+        }
+    })?;
+    let result = run_rollout(rollout_id, caller).await?;
+    log!(
+        &rollout.account.to_string(),
+        rollout.group_id,
+        &caller.to_string(),
+        Action::UpdateProject(
+            rollout.group_id,
+            rollout.project_id,
+            "resume_rollout".to_string()
+        ),
+        rollout_id,
+        &result.status
+    )()
+    .await;
+    Ok(result)
+}
+
+#[update]
+pub async fn rollback_rollout(rollout_id: u64) -> Result<Rollout, ManageError> {
+    let caller = ic_cdk::api::caller();
+    let rollout = rollout_identity_check(rollout_id, caller)?;
+    ROLLOUT_STORAGE.with(|rollout_storage| {
+        match rollout_storage.borrow_mut().get_mut(&rollout_id) {
+            None => Err(ManageError::not_found(ResourceKind::Rollout, rollout_id)),
+            Some(rollout) => rollout.begin_rollback(),
+        }
+    })?;
+    let result = run_rollout_rollback(rollout_id, caller).await?;
+    log!(
+        &rollout.account.to_string(),
+        rollout.group_id,
+        &caller.to_string(),
+        Action::UpdateProject(
+            rollout.group_id,
+            rollout.project_id,
+            "rollback_rollout".to_string()
+        ),
+        rollout_id,
+        &result.status
+    )()
+    .await;
+    Ok(result)
+}
+
+#[query]
+pub fn get_project_rollouts(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+) -> Result<Vec<Rollout>, ManageError> {
+    let caller = ic_cdk::api::caller();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => {
+            user.project_identity_check(group_id, project_id, Permission::ViewStatus, caller)
+        }
+    })?;
+    Ok(ROLLOUT_STORAGE.with(|rollout_storage| {
+        rollout_storage
+            .borrow()
+            .values()
+            .filter(|rollout| {
+                rollout.account == account
+                    && rollout.group_id == group_id
+                    && rollout.project_id == project_id
+            })
+            .cloned()
+            .collect()
+    }))
+}
+
+#[query]
+pub fn get_project_info(
+    account: Principal,
+    group_id: u64,
+    project_id: u64,
+) -> Result<Option<Project>, ManageError> {
+    let caller = ic_cdk::api::caller();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.get_project_info(group_id, project_id, caller),
+    })
+}
+
+#[query]
+pub fn get_group_info(account: Principal, group_id: u64) -> Result<Option<Group>, ManageError> {
+    let caller = ic_cdk::api::caller();
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.get_group_info(group_id, caller),
+    })
+}
+
+#[query]
+pub fn get_group_member_info(
+    account: Principal,
+    group_id: u64,
+    member: Principal,
+) -> Result<Member, ManageError> {
+    USER_STORAGE.with(|user_storage| match user_storage.borrow().get(&account) {
+        None => {
+            return Err(ManageError::not_found(ResourceKind::User, account));
+        }
+        Some(user) => user.get_group_member_info(group_id, member),
+    })
+}
+
+#[pre_upgrade]
+fn pre_upgrade() {
+    unsafe {
+        USER_STORAGE.with(|user_storage| {
+            let data_storage: Vec<(Principal, User)> = user_storage
+                .borrow()
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            let cycle_storage: Vec<((Principal, u64, u64), ProjectCycle)> =
+                CYCLE_STORAGE.with(|cycle_storage| {
+                    cycle_storage
+                        .borrow()
+                        .iter()
+                        .map(|(k, v)| (*k, v.clone()))
+                        .collect()
+                });
+            let upload_storage: Vec<(u64, UploadSession)> = UPLOAD_STORAGE.with(|upload_storage| {
+                upload_storage
+                    .borrow()
+                    .iter()
+                    .map(|(k, v)| (*k, v.clone()))
+                    .collect()
+            });
+            let registry_storage: Vec<((Principal, u64, u64), ModuleRegistry)> = REGISTRY_STORAGE
+                .with(|registry_storage| {
+                    registry_storage
+                        .borrow()
+                        .iter()
+                        .map(|(k, v)| (*k, v.clone()))
+                        .collect()
+                });
+            let wasm_storage: Vec<(Vec<u8>, Vec<u8>)> = WASM_STORAGE.with(|wasm_storage| {
+                wasm_storage
+                    .borrow()
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect()
+            });
+            let install_storage: Vec<(Principal, Vec<InstallRecord>)> =
+                INSTALL_STORAGE.with(|install_storage| {
+                    install_storage
+                        .borrow()
+                        .iter()
+                        .map(|(k, v)| (*k, v.clone()))
+                        .collect()
+                });
+            let rollout_storage: Vec<(u64, Rollout)> = ROLLOUT_STORAGE.with(|rollout_storage| {
+                rollout_storage
+                    .borrow()
+                    .iter()
+                    .map(|(k, v)| (*k, v.clone()))
+                    .collect()
+            });
+            let deletion_storage: Vec<(Principal, DeletionRequest)> =
+                DELETION_STORAGE.with(|deletion_storage| {
+                    deletion_storage
+                        .borrow()
+                        .iter()
+                        .map(|(k, v)| (*k, v.clone()))
+                        .collect()
+                });
+            let transfer_storage: Vec<((Principal, u64), GroupTransfer)> =
+                TRANSFER_STORAGE.with(|transfer_storage| {
+                    transfer_storage
+                        .borrow()
+                        .iter()
+                        .map(|(k, v)| (*k, v.clone()))
+                        .collect()
+                });
+            let invitation_storage: Vec<(u64, Invitation)> =
+                INVITATION_STORAGE.with(|invitation_storage| {
+                    invitation_storage
+                        .borrow()
+                        .iter()
+                        .map(|(k, v)| (*k, v.clone()))
+                        .collect()
+                });
+            let notice_storage: Vec<(Notice_Key, u64)> = NOTICE_STORAGE.with(|notice_storage| {
+                notice_storage
+                    .borrow()
+                    .iter()
+                    .map(|(k, v)| (*k, *v))
+                    .collect()
+            });
+            let membership_index: Vec<(Principal, Membership)> =
+                MEMBERSHIP_INDEX.with(|index| index.borrow().to_vec());
+            let monitor_storage: Vec<(Monitor_Key, Vec<StatusSnapshot>)> =
+                MONITOR_STORAGE.with(|monitor_storage| {
+                    monitor_storage
+                        .borrow()
+                        .iter()
+                        .map(|(k, v)| (*k, v.clone()))
+                        .collect()
+                });
+            ic_cdk::storage::stable_save((
+                OWNER,
+                constant::LOG_CANISTER,
+                data_storage,
+                cycle_storage,
+                constant::MONITOR_INTERVAL,
+                monitor_storage,
+                UPLOAD_ID,
+                upload_storage,
+                registry_storage,
+                install_storage,
+                ROLLOUT_ID,
+                rollout_storage,
+                deletion_storage,
+                transfer_storage,
+                constant::IMAGE_STORE_CANISTER,
+                (
+                    INVITATION_ID,
+                    invitation_storage,
+                    constant::NOTIFICATION_CANISTER,
+                    constant::EXPIRY_NOTICE,
+                    notice_storage,
+                    membership_index,
+                    wasm_storage,
+                ),
+            ))
+            .expect("stable_save failed");
+        })
+    }
+}
+
+// Migrates the stable memory of the first release, which saved only the owner, the log
+// canister and the users. The other storages start empty, the settings keep their defaults
+// and the membership index is rebuilt from the users
+fn migrate_stable_data(legacy: Legacy_Stable_Data) -> Stable_Data {
+    let (owner, log_canister, legacy_users) = legacy;
+    let users: Vec<(Principal, User)> = legacy_users
+        .into_iter()
+        .map(|(account, user)| (account, user.into()))
+        .collect();
+    let mut membership_index = MembershipIndex::default();
+    for (account, user) in users.iter() {
+        for group in user.groups.values() {
+            membership_index.add_group(*account, group);
+        }
+    }
+    unsafe {
+        (
+            owner,
+            log_canister,
+            users,
+            Vec::new(),
+            constant::MONITOR_INTERVAL,
+            Vec::new(),
+            0,
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            0,
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            constant::IMAGE_STORE_CANISTER,
+            (
+                0,
+                Vec::new(),
+                constant::NOTIFICATION_CANISTER,
+                constant::EXPIRY_NOTICE,
+                Vec::new(),
+                membership_index.to_vec(),
+                Vec::new(),
+            ),
+        )
+    }
+}
+
+#[post_upgrade]
+fn post_upgrade() {
+    unsafe {
+        let data_storage = match ic_cdk::storage::stable_restore::<Stable_Data>() {
+            Ok(data_storage) => data_storage,
+            Err(_) => {
+                let legacy: Legacy_Stable_Data =
+                    ic_cdk::storage::stable_restore().expect("data recovery failed");
+                migrate_stable_data(legacy)
+            }
+        };
+        OWNER = data_storage.0;
+        constant::LOG_CANISTER = data_storage.1;
+        constant::MONITOR_INTERVAL = data_storage.4;
+        UPLOAD_ID = data_storage.6;
+        let upload_storage: Upload_Storage = data_storage.7.into_iter().collect();
+        let registry_storage: Registry_Storage = data_storage.8.into_iter().collect();
+        let install_storage: Install_Storage = data_storage.9.into_iter().collect();
+        ROLLOUT_ID = data_storage.10;
+        let rollout_storage: Rollout_Storage = data_storage.11.into_iter().collect();
+        let deletion_storage: Deletion_Storage = data_storage.12.into_iter().collect();
+        let transfer_storage: Transfer_Storage = data_storage.13.into_iter().collect();
+        constant::IMAGE_STORE_CANISTER = data_storage.14;
+        INVITATION_ID = (data_storage.15).0;
+        let invitation_storage: Invitation_Storage = (data_storage.15).1.into_iter().collect();
+        constant::NOTIFICATION_CANISTER = (data_storage.15).2;
+        constant::EXPIRY_NOTICE = (data_storage.15).3;
+        let notice_storage: Notice_Storage = (data_storage.15).4.into_iter().collect();
+        let membership_index = MembershipIndex::from_vec((data_storage.15).5);
+        let wasm_storage: Wasm_Storage = (data_storage.15).6.into_iter().collect();
+        let monitor_storage: Monitor_Storage = data_storage.5.into_iter().collect();
+        let cycle_storage: Cycle_Storage = data_storage.3.into_iter().collect();
+        let data_storage: User_Storage = data_storage.2.into_iter().collect();
+        USER_STORAGE.with(|user_storage| {
+            *user_storage.borrow_mut() = data_storage;
+        });
+        CYCLE_STORAGE.with(|storage| {
+            *storage.borrow_mut() = cycle_storage;
+        });
+        MONITOR_STORAGE.with(|storage| {
+            *storage.borrow_mut() = monitor_storage;
+        });
+        UPLOAD_STORAGE.with(|storage| {
+            *storage.borrow_mut() = upload_storage;
+        });
+        REGISTRY_STORAGE.with(|storage| {
+            *storage.borrow_mut() = registry_storage;
+        });
+        WASM_STORAGE.with(|storage| {
+            *storage.borrow_mut() = wasm_storage;
+        });
+        INSTALL_STORAGE.with(|storage| {
+            *storage.borrow_mut() = install_storage;
+        });
+        ROLLOUT_STORAGE.with(|storage| {
+            *storage.borrow_mut() = rollout_storage;
+        });
+        DELETION_STORAGE.with(|storage| {
+            *storage.borrow_mut() = deletion_storage;
+        });
+        TRANSFER_STORAGE.with(|storage| {
+            *storage.borrow_mut() = transfer_storage;
+        });
+        INVITATION_STORAGE.with(|storage| {
+            *storage.borrow_mut() = invitation_storage;
+        });
+        NOTICE_STORAGE.with(|storage| {
+            *storage.borrow_mut() = notice_storage;
+        });
+        MEMBERSHIP_INDEX.with(|storage| {
+            *storage.borrow_mut() = membership_index;
+        });
+    }
+}
+
+#[cfg(test)]
+mod test_util {
+    use super::*;
+    #[test]
+    fn test_is_controller() {
+        let err = String::from("get_canister_status faile: 5: Only the controllers of the canister r7inp-6aaaa-aaaaa-aaabq-cai can control it.\nCanister\'s controllers: rrkah-fqaaa-aaaaa-aaaaq-cai dzhx6-f63tz-aslp6-xxyzd-pknwt-lxpho-q2wsx-pvwwd-v3nq6-75ek5-rqe\nSender\'s ID: r7inp-6aaaa-aaaaa-aaabq-cai");
+        let controller = Principal::from_text("r7inp-6aaaa-aaaaa-aaabq-cai").unwrap();
+        assert_eq!(util::is_controller(err.clone(), controller), false);
+
+        let controller = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        assert_eq!(util::is_controller(err, controller), true);
+    }
+
+    #[test]
+    fn test_change_controllers() {
+        let manage = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        let user =
+            Principal::from_text("dzhx6-f63tz-aslp6-xxyzd-pknwt-lxpho-q2wsx-pvwwd-v3nq6-75ek5-rqe")
+                .unwrap();
+        let other = Principal::from_text("r7inp-6aaaa-aaaaa-aaabq-cai").unwrap();
+
+        let controllers =
+            util::change_controllers(vec![manage, user], other, true, manage).unwrap();
+        assert_eq!(controllers, vec![manage, user, other]);
+        assert!(util::change_controllers(controllers.clone(), other, true, manage).is_err());
+        assert!(util::change_controllers(controllers.clone(), manage, false, manage).is_err());
+        assert!(util::change_controllers(controllers.clone(), user, false, manage).is_err());
+        assert_eq!(
+            util::change_controllers(controllers, other, false, manage).unwrap(),
+            vec![manage, user]
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_permission {
+    use super::*;
+    use project::ProjectType;
+    use types::Profile;
+
+    #[test]
+    fn test_permission_resolver() {
+        let lead =
+            Principal::from_text("dzhx6-f63tz-aslp6-xxyzd-pknwt-lxpho-q2wsx-pvwwd-v3nq6-75ek5-rqe")
+                .unwrap();
+        let dev = Principal::from_text("r7inp-6aaaa-aaaaa-aaabq-cai").unwrap();
+        let contractor = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        let canister = Principal::from_text("rkp4c-7iaaa-aaaaa-aaaca-cai").unwrap();
+
+        let mut project = Project::new(
+            1,
+            0,
+            1,
+            "project",
+            "",
+            lead,
+            "",
+            Profile::Private,
+            vec![
+                Member::new("dev".to_string(), Authority::Read, dev, 0, None, None),
+                Member::new(
+                    "contractor".to_string(),
+                    Authority::Read,
+                    contractor,
+                    0,
+                    None,
+                    None,
+                ),
+            ],
+            Nat::from(0),
+            &[canister],
+            ProjectType::Tools,
+        );
+        project
+            .update_canister_acl(canister, contractor, Some(vec![Permission::InstallCode]))
+            .unwrap();
+        let mut group = Group::new(
+            1,
+            0,
+            Profile::Private,
+            "group",
+            "",
+            vec![project],
+            vec![
+                Member::new(
+                    "lead".to_string(),
+                    Authority::Operational,
+                    lead,
+                    0,
+                    None,
+                    None,
+                ),
+                Member::new("dev".to_string(), Authority::Write, dev, 0, None, None),
+            ],
+            String::new(),
+        );
+
+        // Group authority is inherited by the projects of the group
+        assert!(group
+            .project_identity_check(1, Permission::Delete, lead)
+            .is_ok());
+        // A project entry overrides the group entry
+        assert!(group
+            .project_identity_check(1, Permission::EditMetadata, dev)
+            .is_err());
+        assert!(group.identity_check(Permission::EditMetadata, dev).is_ok());
+        // A canister acl entry only applies to its canister
+        assert!(group
+            .canister_identity_check(1, canister, Permission::InstallCode, contractor)
+            .is_ok());
+        assert!(group
+            .canister_identity_check(1, canister, Permission::Delete, contractor)
+            .is_err());
+        assert!(group
+            .project_identity_check(1, Permission::InstallCode, contractor)
+            .is_err());
+        // The acl entry goes with the membership
+        group.remove_project_member(1, contractor, lead).unwrap();
+        assert!(group
+            .canister_identity_check(1, canister, Permission::InstallCode, contractor)
+            .is_err());
+        assert!(group.projects[&1].canister_acls.is_empty());
+
+        // Roles add permissions on top of the built-in authority
+        group
+            .create_role(Role::new(
+                "release-manager".to_string(),
+                String::new(),
+                vec![Permission::InstallCode],
+            ))
+            .unwrap();
+        group
+            .update_project_member_role(1, dev, Some("release-manager".to_string()), lead)
+            .unwrap();
+        assert!(group
+            .project_identity_check(1, Permission::InstallCode, dev)
+            .is_ok());
+        assert!(group.delete_role("release-manager").is_err());
+        // Only permissions the sender holds can be handed out
+        assert!(group
+            .grant_check(None, &[Permission::EditMetadata], dev)
+            .is_ok());
+        assert!(group
+            .grant_check(None, &[Permission::ManageMembers], dev)
+            .is_err());
+
+        // The owner of the group holds every permission without a member entry
+        let owner = Principal::from_text("renrk-eyaaa-aaaaa-aaada-cai").unwrap();
+        assert!(group
+            .canister_identity_check(1, canister, Permission::Delete, owner)
+            .is_err());
+        group.owner = Some(owner);
+        assert!(group
+            .canister_identity_check(1, canister, Permission::Delete, owner)
+            .is_ok());
+        assert!(group
+            .grant_check(Some(1), &[Permission::ManageMembers], owner)
+            .is_ok());
+        assert!(group
+            .project_identity_check(2, Permission::ViewStatus, owner)
+            .is_err());
+    }
+
+    #[test]
+    fn test_canister_acl_grant() {
+        let lead = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        let contractor = Principal::from_text("r7inp-6aaaa-aaaaa-aaabq-cai").unwrap();
+        let canister = Principal::from_text("rkp4c-7iaaa-aaaaa-aaaca-cai").unwrap();
+        let project = Project::new(
+            1,
+            0,
+            1,
+            "project",
+            "",
+            lead,
+            "",
+            Profile::Public,
+            vec![Member::new(
+                "contractor".to_string(),
+                Authority::Read,
+                contractor,
+                0,
+                None,
+                None,
+            )],
+            Nat::from(0),
+            &[canister],
+            ProjectType::Tools,
+        );
+        let mut group = Group::new(
+            1,
+            0,
+            Profile::Public,
+            "group",
+            "",
+            vec![project],
+            vec![Member::new(
+                "lead".to_string(),
+                Authority::Write,
+                lead,
+                0,
+                None,
+                Some("member-manager".to_string()),
+            )],
+            String::new(),
+        );
+        group.roles.insert(
+            "member-manager".to_string(),
+            Role::new(
+                "member-manager".to_string(),
+                String::new(),
+                vec![Permission::ManageMembers],
+            ),
+        );
+
+        // A ManageMembers holder can only hand out the permissions it holds itself
+        assert!(group
+            .update_canister_acl(
+                1,
+                canister,
+                contractor,
+                Some(vec![Permission::Delete]),
+                lead
+            )
+            .is_err());
+        assert!(group.projects[&1].canister_acls.is_empty());
+        assert!(group
+            .update_canister_acl(1, canister, contractor, Some(vec![Permission::TopUp]), lead)
+            .is_ok());
+        assert!(group
+            .update_canister_acl(1, canister, contractor, None, lead)
+            .is_ok());
+        assert!(group.projects[&1].canister_acls.is_empty());
+    }
+
+    #[test]
+    fn test_add_project() {
+        let lead = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        let contractor = Principal::from_text("r7inp-6aaaa-aaaaa-aaabq-cai").unwrap();
+        let project = |in_group: u64| {
+            let mut project = Project::new(
+                2,
+                0,
+                in_group,
+                "project",
+                "",
+                contractor,
+                "",
+                Profile::Public,
+                vec![Member::new(
+                    "contractor".to_string(),
+                    Authority::Operational,
+                    contractor,
+                    0,
+                    None,
+                    None,
+                )],
+                Nat::from(0),
+                &[],
+                ProjectType::Tools,
+            );
+            project.canister_acls.insert(
+                lead,
+                vec![(contractor, vec![Permission::Delete])]
+                    .into_iter()
+                    .collect(),
+            );
+            project
+        };
+        let mut group = Group::new(
+            1,
+            0,
+            Profile::Public,
+            "group",
+            "",
+            Vec::new(),
+            vec![Member::new(
+                "lead".to_string(),
+                Authority::Operational,
+                lead,
+                0,
+                None,
+                None,
+            )],
+            String::new(),
+        );
+
+        assert!(group.add_project(project(3), lead).is_err());
+        // Members and acls of the new project only come through invitations
+        group.add_project(project(1), lead).unwrap();
+        let added = &group.projects[&2];
+        assert!(added.members.is_empty());
+        assert!(added.canister_acls.is_empty());
+        assert_eq!(added.create_by, lead);
+    }
+
+    #[test]
+    fn test_remove_expired_members() {
+        let lead = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        let contractor = Principal::from_text("r7inp-6aaaa-aaaaa-aaabq-cai").unwrap();
+        let project = Project::new(
+            1,
+            0,
+            1,
+            "project",
+            "",
+            lead,
+            "",
+            Profile::Public,
+            vec![Member::new(
+                "contractor".to_string(),
+                Authority::Write,
+                contractor,
+                0,
+                Some(5),
+                None,
+            )],
+            Nat::from(0),
+            &[],
+            ProjectType::Tools,
+        );
+        let mut group = Group::new(
+            1,
+            0,
+            Profile::Public,
+            "group",
+            "",
+            vec![project],
+            vec![
+                Member::new(
+                    "lead".to_string(),
+                    Authority::Operational,
+                    lead,
+                    0,
+                    None,
+                    None,
+                ),
+                Member::new(
+                    "contractor".to_string(),
+                    Authority::Read,
+                    contractor,
+                    0,
+                    Some(20),
+                    None,
+                ),
+            ],
+            String::new(),
+        );
+
+        assert_eq!(
+            group.remove_expired_members(10),
+            vec![(Some(1), contractor)]
+        );
+        assert!(group.members.contains_key(&contractor));
+        assert_eq!(group.remove_expired_members(30), vec![(None, contractor)]);
+        assert!(group.members.contains_key(&lead));
+    }
+
+    #[test]
+    fn test_membership_index() {
+        let owner = Principal::from_text("rkp4c-7iaaa-aaaaa-aaaca-cai").unwrap();
+        let new_owner = Principal::from_text("renrk-eyaaa-aaaaa-aaada-cai").unwrap();
+        let dev = Principal::from_text("r7inp-6aaaa-aaaaa-aaabq-cai").unwrap();
+        let project = Project::new(
+            2,
+            0,
+            1,
+            "project",
+            "",
+            owner,
+            "",
+            Profile::Public,
+            vec![Member::new(
+                "dev".to_string(),
+                Authority::Operational,
+                dev,
+                0,
+                None,
+                None,
+            )],
+            Nat::from(0),
+            &[],
+            ProjectType::Tools,
+        );
+        let group = Group::new(
+            1,
+            0,
+            Profile::Public,
+            "group",
+            "",
+            vec![project],
+            vec![Member::new(
+                "dev".to_string(),
+                Authority::Read,
+                dev,
+                0,
+                None,
+                None,
+            )],
+            String::new(),
+        );
+
+        let mut index = MembershipIndex::default();
+        index.add_group(owner, &group);
+        assert_eq!(index.get(dev).len(), 2);
+        // The project entry gives its own authority
+        let info = group.membership_info(owner, Some(2), dev, 0).unwrap();
+        assert!(info.permissions.contains(&Permission::Delete));
+
+        index.move_group(owner, new_owner, &group);
+        assert!(index
+            .get(dev)
+            .iter()
+            .all(|membership| membership.owner == new_owner));
+        index.remove(dev, &Membership::new(new_owner, 1, Some(2)));
+        assert_eq!(index.get(dev), vec![Membership::new(new_owner, 1, None)]);
+        index.remove_group(new_owner, &group);
+        assert!(index.get(dev).is_empty());
+    }
+}
+
+#[cfg(test)]
+mod test_transaction {
+    use super::*;
+
+    fn group(id: u64, name: &str) -> Group {
+        Group::new(
+            id,
+            0,
+            Profile::Public,
+            name,
+            "",
+            Vec::new(),
+            Vec::new(),
+            String::new(),
+        )
+    }
+
+    fn group_name(owner: Principal, group_id: u64) -> Option<String> {
+        USER_STORAGE.with(|user_storage| {
+            user_storage.borrow()[&owner]
+                .groups
+                .get(&group_id)
+                .map(|group| group.name.clone())
+        })
+    }
+
+    #[test]
+    fn test_failed_transaction_changes_nothing() {
+        let owner = Principal::from_text("rkp4c-7iaaa-aaaaa-aaaca-cai").unwrap();
+        let missing = Principal::from_text("renrk-eyaaa-aaaaa-aaada-cai").unwrap();
+        let member = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        let mut user = User::new("owner".to_string(), Profile::Public, owner, 0);
+        user.groups.insert(1, group(1, "first"));
+        user.groups.insert(2, group(2, "second"));
+        USER_STORAGE.with(|user_storage| {
+            user_storage.borrow_mut().insert(owner, user);
+        });
+
+        let res: Result<(), ManageError> = with_users(|users| {
+            users.group_mut(owner, 1)?.name = "renamed".to_string();
+            insert_membership(users, member, Membership::new(owner, 1, None));
+            users.user_mut(missing, 1)?;
+            Ok(())
+        });
+        assert!(res.is_err());
+        assert_eq!(group_name(owner, 1), Some("first".to_string()));
+        assert!(MEMBERSHIP_INDEX.with(|index| index.borrow().get(member).is_empty()));
+
+        // Only the staged group is written back, the other groups of the user are kept
+        with_users(|users| {
+            users.group_mut(owner, 1)?.name = "renamed".to_string();
+            insert_membership(users, member, Membership::new(owner, 1, None));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(group_name(owner, 1), Some("renamed".to_string()));
+        assert_eq!(group_name(owner, 2), Some("second".to_string()));
+        assert_eq!(
+            MEMBERSHIP_INDEX.with(|index| index.borrow().get(member)),
+            vec![Membership::new(owner, 1, None)]
+        );
+
+        with_user(owner, 2, |user| {
+            user.groups.remove(&2);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(group_name(owner, 2), None);
+        assert_eq!(group_name(owner, 1), Some("renamed".to_string()));
+    }
+}
+
+#[cfg(test)]
+mod test_migration {
+    use super::*;
+    use project::ProjectType;
+
+    // Records as the first release encoded them
+    #[derive(CandidType)]
+    struct BaselineMember {
+        name: String,
+        authority: Authority,
+        identity: Principal,
+        join_time: u64,
+        expiration_time: Option<u64>,
+    }
+
+    #[derive(CandidType)]
+    struct BaselineProject {
+        id: u64,
+        create_time: u64,
+        in_group: u64,
+        visibility: Profile,
+        create_by: Principal,
+        name: String,
+        description: String,
+        git_repo_url: String,
+        members: HashMap<Principal, BaselineMember>,
+        canister_cycle_floor: Nat,
+        canisters: Vec<Principal>,
+        function: ProjectType,
+    }
+
+    #[derive(CandidType)]
+    struct BaselineGroup {
+        id: u64,
+        create_time: u64,
+        visibility: Profile,
+        name: String,
+        description: String,
+        projects: HashMap<u64, BaselineProject>,
+        members: HashMap<Principal, BaselineMember>,
+        url: String,
+    }
+
+    #[derive(CandidType)]
+    struct RelationProject {
+        group_id: u64,
+        project_id: u64,
+    }
+
+    #[derive(CandidType)]
+    struct BaselineUser {
+        user_name: String,
+        profile: Profile,
+        identity: Principal,
+        groups: HashMap<u64, BaselineGroup>,
+        relation_project: HashMap<Principal, Vec<RelationProject>>,
+        create_time: u64,
+    }
+
+    fn member(identity: Principal) -> BaselineMember {
+        BaselineMember {
+            name: String::from("member"),
+            authority: Authority::Write,
+            identity: identity,
+            join_time: 1,
+            expiration_time: None,
+        }
+    }
+
+    #[test]
+    fn test_migrate_baseline() {
+        let owner = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        let account =
+            Principal::from_text("dzhx6-f63tz-aslp6-xxyzd-pknwt-lxpho-q2wsx-pvwwd-v3nq6-75ek5-rqe")
+                .unwrap();
+        let other = Principal::from_text("r7inp-6aaaa-aaaaa-aaabq-cai").unwrap();
+
+        let project = BaselineProject {
+            id: 2,
+            create_time: 1,
+            in_group: 1,
+            visibility: Profile::Public,
+            create_by: account,
+            name: String::from("project"),
+            description: String::new(),
+            git_repo_url: String::new(),
+            members: vec![(other, member(other))].into_iter().collect(),
+            canister_cycle_floor: Nat::from(0),
+            canisters: vec![other],
+            function: ProjectType::Dapp,
+        };
+        let group = BaselineGroup {
+            id: 1,
+            create_time: 1,
+            visibility: Profile::Public,
+            name: String::from("group"),
+            description: String::new(),
+            projects: vec![(2, project)].into_iter().collect(),
+            members: vec![(account, member(account))].into_iter().collect(),
+            url: String::new(),
+        };
+        let user = BaselineUser {
+            user_name: String::from("user"),
+            profile: Profile::Public,
+            identity: account,
+            groups: vec![(1, group)].into_iter().collect(),
+            relation_project: vec![(
+                other,
+                vec![RelationProject {
+                    group_id: 1,
+                    project_id: 2,
+                }],
+            )]
+            .into_iter()
+            .collect(),
+            create_time: 1,
+        };
+        let bytes = candid::encode_args((owner, owner, vec![(account, user)])).unwrap();
+
+        assert!(candid::decode_args::<Stable_Data>(&bytes).is_err());
+        let legacy: Legacy_Stable_Data = candid::decode_args(&bytes).unwrap();
+        let data = migrate_stable_data(legacy);
+        assert_eq!(data.0, owner);
+        let (_, user) = &data.2[0];
+        let group = &user.groups[&1];
+        assert_eq!(group.owner, Some(account));
+        assert!(group.roles.is_empty());
+        let project = &group.projects[&2];
+        assert_eq!(project.canisters, vec![other]);
+        assert!(project.installed_modules.is_empty());
+        assert!(project.controllability.is_empty());
+        assert!(project.canister_acls.is_empty());
+        assert!(project.members[&other].role.is_none());
+
+        let index = MembershipIndex::from_vec((data.15).5);
+        assert_eq!(index.get(account), vec![Membership::new(account, 1, None)]);
+        assert_eq!(index.get(other), vec![Membership::new(account, 1, Some(2))]);
+    }
+}
+
+#[cfg(test)]
+mod test_deletion {
+    use super::*;
+
+    #[test]
+    fn test_confirm() {
+        let canister = Principal::from_text("rkp4c-7iaaa-aaaaa-aaaca-cai").unwrap();
+        let account = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        let mut request = DeletionRequest::new(canister, account, 1, 2, account, 10, None);
+
+        // The cooldown must be over
+        assert!(request
+            .confirm(10 + constant::DELETION_COOLDOWN - 1)
+            .is_err());
+        assert_eq!(request.status, DeletionStatus::Pending);
+        request.confirm(10 + constant::DELETION_COOLDOWN).unwrap();
+        assert_eq!(request.status, DeletionStatus::InProgress);
+        // A deletion in progress can not be confirmed again
+        assert!(request.confirm(10 + constant::DELETION_COOLDOWN).is_err());
+
+        // A failed deletion goes back to pending and can be confirmed again
+        request.fail(ManageError::InvalidState("stop failed".to_string()));
+        assert_eq!(request.status, DeletionStatus::Pending);
+        assert!(request.last_error.is_some());
+        request.confirm(10 + constant::DELETION_COOLDOWN).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod test_rollout {
+    use super::*;
+
+    #[test]
+    fn test_rollout_state() {
+        let account = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        let first = Principal::from_text("rkp4c-7iaaa-aaaaa-aaaca-cai").unwrap();
+        let second = Principal::from_text("r7inp-6aaaa-aaaaa-aaabq-cai").unwrap();
+        let third = Principal::from_text("renrk-eyaaa-aaaaa-aaada-cai").unwrap();
+        let mut rollout = Rollout::new(
+            1,
+            account,
+            1,
+            2,
+            account,
+            0,
+            "module".to_string(),
+            "1.0.0".to_string(),
+            Vec::new(),
+            vec![vec![first], vec![second, third]],
+        );
+        let err = ManageError::InvalidState("failed".to_string());
+
+        // A wave is done once all of its canisters succeeded
+        assert_eq!(rollout.next_wave(), Some((0, vec![first])));
+        rollout.update_step(first, StepStatus::Succeeded, 1);
+        assert_eq!(rollout.next_wave(), Some((1, vec![second, third])));
+        assert!(rollout.resume().is_err());
+
+        // An installed canister failing its check is retried and rolled back
+        rollout.update_step(second, StepStatus::CheckFailed(err.clone()), 2);
+        rollout.update_step(third, StepStatus::Failed(err.clone()), 2);
+        rollout.status = RolloutStatus::Halted;
+        assert_eq!(rollout.next_wave(), Some((1, vec![second, third])));
+        assert_eq!(rollout.rollback_canisters(), vec![second, first]);
+        rollout.resume().unwrap();
+        assert_eq!(rollout.status, RolloutStatus::InProgress);
+        assert!(rollout.begin_rollback().is_err());
+
+        // A partial rollback can only be retried, with the canisters that failed to roll back
+        rollout.status = RolloutStatus::Halted;
+        rollout.begin_rollback().unwrap();
+        rollout.update_step(second, StepStatus::RolledBack, 3);
+        rollout.update_step(first, StepStatus::RollbackFailed(err), 3);
+        rollout.status = RolloutStatus::RollbackHalted;
+        assert!(rollout.resume().is_err());
+        assert_eq!(rollout.rollback_canisters(), vec![first]);
+        rollout.begin_rollback().unwrap();
+        rollout.status = RolloutStatus::RolledBack;
+        assert!(rollout.resume().is_err());
+        assert!(rollout.begin_rollback().is_err());
+    }
+}
+
+#[cfg(test)]
+mod test_manage {
+    use super::*;
+    use manage::CanisterSettings;
+
+    #[test]
+    fn test_validate_settings() {
+        let manage = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        let user =
+            Principal::from_text("dzhx6-f63tz-aslp6-xxyzd-pknwt-lxpho-q2wsx-pvwwd-v3nq6-75ek5-rqe")
+                .unwrap();
+        let other = Principal::from_text("r7inp-6aaaa-aaaaa-aaabq-cai").unwrap();
+        let controllers = |controllers: Vec<Principal>| {
+            CanisterSettings::new(Some(controllers), None, None, None).validate(manage)
+        };
+
+        assert!(controllers(vec![manage, user]).is_ok());
+        assert!(controllers(Vec::new()).is_err());
+        assert!(controllers(vec![user]).is_err());
+        // No human controller would be left
+        assert!(controllers(vec![manage]).is_err());
+        assert!(controllers(vec![manage, other]).is_err());
+        assert!(controllers(vec![user; constant::MAX_CONTROLLERS + 1]).is_err());
+
+        let bounds = |compute: u64, memory: u64, freezing: u64| {
+            CanisterSettings::new(
+                None,
+                Some(Nat::from(compute)),
+                Some(Nat::from(memory)),
+                Some(Nat::from(freezing)),
+            )
+            .validate(manage)
+        };
+        assert!(bounds(
+            constant::MAX_COMPUTE_ALLOCATION,
+            constant::MAX_MEMORY_ALLOCATION,
+            constant::MAX_FREEZING_THRESHOLD
+        )
+        .is_ok());
+        assert!(bounds(constant::MAX_COMPUTE_ALLOCATION + 1, 0, 0).is_err());
+        assert!(bounds(0, constant::MAX_MEMORY_ALLOCATION + 1, 0).is_err());
+        assert!(bounds(0, 0, constant::MAX_FREEZING_THRESHOLD + 1).is_err());
+    }
+}
+
+#[cfg(test)]
+mod test_registry {
+    use super::*;
+
+    fn module(version: usize) -> WasmModule {
+        let uploader = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        WasmModule::new(
+            "module".to_string(),
+            version.to_string(),
+            vec![version as u8],
+            uploader,
+            String::new(),
+            0,
+            1,
+        )
+    }
+
+    #[test]
+    fn test_publish() {
+        let mut registry = ModuleRegistry::default();
+        registry.publish(module(0)).unwrap();
+        // A version is only published once
+        assert!(registry.publish_check("module", "0").is_err());
+        assert!(registry.publish(module(0)).is_err());
+        assert!(registry.get("module", "0").is_some());
+
+        // The number of versions is capped, removing one makes room again
+        for version in 1..constant::REGISTRY_VERSION_LIMIT {
+            registry.publish(module(version)).unwrap();
+        }
+        let next = constant::REGISTRY_VERSION_LIMIT.to_string();
+        assert!(registry.publish_check("module", &next).is_err());
+        registry.remove("module", "0").unwrap();
+        assert!(registry.remove("module", "0").is_err());
+        assert!(registry.publish_check("module", &next).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod test_notification {
+    use super::*;
+
+    #[test]
+    fn test_pending_notices() {
+        let account = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        let identity = Principal::from_text("r7inp-6aaaa-aaaaa-aaabq-cai").unwrap();
+        let expiring = |expiration_time: u64| {
+            let member = Member::new(
+                "contractor".to_string(),
+                Authority::Read,
+                identity,
+                0,
+                Some(expiration_time),
+                None,
+            );
+            vec![ExpiringMember::new(account, 1, Some(2), member)]
+        };
+        let mut notice_storage = Notice_Storage::new();
+
+        assert_eq!(
+            pending_notices(&mut notice_storage, expiring(100), 10).len(),
+            1
+        );
+        // A member is notified once per expiration time
+        assert!(pending_notices(&mut notice_storage, expiring(100), 20).is_empty());
+        // and again once it is renewed
+        assert_eq!(
+            pending_notices(&mut notice_storage, expiring(200), 30).len(),
+            1
+        );
+        assert_eq!(notice_storage[&(account, 1, Some(2), identity)], 200);
+        // Notices of past expirations are dropped
+        assert!(pending_notices(&mut notice_storage, Vec::new(), 300).is_empty());
+        assert!(notice_storage.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod test_upload {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    #[test]
+    fn test_finalize() {
+        let uploader = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        let other = Principal::from_text("r7inp-6aaaa-aaaaa-aaabq-cai").unwrap();
+        let mut session = UploadSession::new(1, uploader, uploader, 1, 2, 10);
+
+        // Only the uploader can add chunks, up to the size cap
+        assert!(session.append_chunk(&[1, 2], other).is_err());
+        assert_eq!(session.append_chunk(&[1, 2], uploader).unwrap(), 2);
+        assert!(session
+            .append_chunk(&vec![0; constant::WASM_MAX_SIZE - 1], uploader)
+            .is_err());
+        assert!(session.module(uploader, 1, 2).is_err());
+
+        // The declared hash must match the uploaded module
+        let hash = Sha256::digest(&[1u8, 2]).to_vec();
+        assert!(matches!(
+            session.finalize(vec![0; 32], uploader),
+            Err(ManageError::HashMismatch { .. })
+        ));
+        assert!(session.finalize(hash.clone(), other).is_err());
+        session.finalize(hash.clone(), uploader).unwrap();
+        assert!(session.finalize(hash, uploader).is_err());
+        assert!(session.append_chunk(&[3], uploader).is_err());
+        assert_eq!(session.module(uploader, 1, 2).unwrap(), &[1, 2]);
+        assert!(session.module(uploader, 1, 3).is_err());
+    }
+
+    #[test]
+    fn test_expiration() {
+        let uploader = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        let mut session = UploadSession::new(1, uploader, uploader, 1, 2, 10);
+        assert!(!session.is_expired(10 + constant::UPLOAD_EXPIRATION));
+        assert!(session.is_expired(10 + constant::UPLOAD_EXPIRATION + 1));
+
+        // A finalized upload is kept longer, but not forever
+        session.append_chunk(&[1], uploader).unwrap();
+        session
+            .finalize(Sha256::digest(&[1u8]).to_vec(), uploader)
+            .unwrap();
+        assert!(!session.is_expired(10 + constant::UPLOAD_EXPIRATION + 1));
+        assert!(session.is_expired(10 + constant::FINALIZED_UPLOAD_EXPIRATION + 1));
+    }
+}
+
+#[cfg(test)]
+mod test_rollback {
+    use super::*;
+
+    #[test]
+    fn test_install_history() {
+        let installer = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        let record =
+            |time: u64| InstallRecord::new(&[time as u8], Vec::new(), installer, time, None);
+        let mut history = Vec::new();
+
+        rollback::push_record(&mut history, record(1));
+        // A single install has nothing to roll back to
+        assert!(rollback::previous_record(&history).is_err());
+        rollback::push_record(&mut history, record(2));
+        assert_eq!(rollback::previous_record(&history).unwrap().install_time, 1);
+
+        // The oldest records are dropped past the cap
+        for time in 3..=(constant::INSTALL_HISTORY_LIMIT as u64 + 3) {
+            rollback::push_record(&mut history, record(time));
+        }
+        let last = constant::INSTALL_HISTORY_LIMIT as u64 + 3;
+        assert_eq!(history.len(), constant::INSTALL_HISTORY_LIMIT);
+        assert_eq!(history.last().unwrap().install_time, last);
+        assert_eq!(
+            rollback::previous_record(&history).unwrap().install_time,
+            last - 1
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_transfer {
+    use super::*;
+
+    #[test]
+    fn test_transfer_group() {
+        let from = Principal::from_text("rkp4c-7iaaa-aaaaa-aaaca-cai").unwrap();
+        let to = Principal::from_text("renrk-eyaaa-aaaaa-aaada-cai").unwrap();
+        let other = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        let member = Principal::from_text("r7inp-6aaaa-aaaaa-aaabq-cai").unwrap();
+        let group = |id: u64| {
+            let mut group = Group::new(
+                id,
+                0,
+                Profile::Public,
+                "group",
+                "",
+                Vec::new(),
+                vec![Member::new(
+                    "member".to_string(),
+                    Authority::Read,
+                    member,
+                    0,
+                    None,
+                    None,
+                )],
+                String::new(),
+            );
+            group.owner = Some(from);
+            group
+        };
+        let mut sender = User::new("from".to_string(), Profile::Public, from, 0);
+        sender.groups.insert(1, group(1));
+        sender.groups.insert(2, group(2));
+        let mut recipient = User::new("to".to_string(), Profile::Public, to, 0);
+        recipient.groups.insert(2, group(2));
+        USER_STORAGE.with(|user_storage| {
+            let mut user_storage = user_storage.borrow_mut();
+            user_storage.insert(from, sender);
+            user_storage.insert(to, recipient);
+        });
+        MEMBERSHIP_INDEX.with(|index| index.borrow_mut().add_group(from, &group(1)));
+        TRANSFER_STORAGE.with(|transfer_storage| {
+            let mut transfer_storage = transfer_storage.borrow_mut();
+            transfer_storage.insert((from, 1), GroupTransfer::new(from, 1, to, 0));
+            transfer_storage.insert((from, 2), GroupTransfer::new(from, 2, to, 0));
+        });
+
+        // Only the recipient of an offer can accept it
+        assert!(transfer_group(from, 1, other).is_err());
+        assert!(transfer_group(from, 3, to).is_err());
+        // A recipient already holding a group with the same id is rejected and nothing moves
+        assert!(matches!(
+            transfer_group(from, 2, to),
+            Err(ManageError::AlreadyExists { .. })
+        ));
+        USER_STORAGE.with(|user_storage| {
+            assert!(user_storage.borrow()[&from].groups.contains_key(&2));
+        });
+
+        transfer_group(from, 1, to).unwrap();
+        USER_STORAGE.with(|user_storage| {
+            let user_storage = user_storage.borrow();
+            assert!(!user_storage[&from].groups.contains_key(&1));
+            assert_eq!(user_storage[&to].groups[&1].owner, Some(to));
+        });
+        assert!(TRANSFER_STORAGE
+            .with(|transfer_storage| !transfer_storage.borrow().contains_key(&(from, 1))));
+        assert_eq!(
+            MEMBERSHIP_INDEX.with(|index| index.borrow().get(member)),
+            vec![Membership::new(to, 1, None)]
+        );
+        // The offer is gone once accepted
+        assert!(transfer_group(from, 1, to).is_err());
     }
 }
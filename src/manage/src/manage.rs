@@ -1,4 +1,7 @@
-use ic_cdk::api::call::call;
+use crate::constant;
+use crate::error::ManageError;
+use crate::util;
+use ic_cdk::api::call::{call, call_with_payment128};
 use ic_cdk::export::candid::Nat;
 use ic_cdk::export::candid::{CandidType, Deserialize};
 use ic_cdk::export::Principal;
@@ -29,6 +32,67 @@ pub enum InstallCodeMode {
     Upgrade,
 }
 
+#[derive(CandidType, Debug, Deserialize)]
+pub struct InstallCodeArgument {
+    pub mode: InstallCodeMode,
+    pub canister_id: Principal,
+    pub wasm_module: Vec<u8>,
+    pub arg: Vec<u8>,
+}
+
+// Whether the manage canister can operate a registered canister, checked on registration
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct Controllability {
+    pub controllers: Vec<Principal>,
+    pub manage_canister_is_controller: bool,
+    pub registrant: Principal,
+    pub registrant_is_controller: bool,
+    pub check_time: u64,
+}
+
+impl Controllability {
+    pub fn new(controllers: Vec<Principal>, registrant: Principal, check_time: u64) -> Self {
+        Self {
+            manage_canister_is_controller: controllers.contains(&ic_cdk::api::id()),
+            registrant_is_controller: controllers.contains(&registrant),
+            controllers: controllers,
+            registrant: registrant,
+            check_time: check_time,
+        }
+    }
+
+    // Ask the management canister for the controllers, a non-controller gets them in the reject message
+    pub async fn check(canister: Principal, registrant: Principal) -> Result<Self, ManageError> {
+        let controllers = match ManageCanister::get_canister_status(canister, Nat::default()).await
+        {
+            Ok((status, _)) => status.settings.controllers.unwrap_or_default(),
+            Err(err) => {
+                let controllers = match &err {
+                    ManageError::ManagementCallFailed { msg, .. } => util::parse_controllers(msg),
+                    _ => Vec::new(),
+                };
+                if controllers.is_empty() {
+                    return Err(err);
+                }
+                controllers
+            }
+        };
+        Ok(Self::new(controllers, registrant, ic_cdk::api::time()))
+    }
+}
+
+#[derive(CandidType, Debug, Deserialize)]
+pub struct CreateCanisterArgument {
+    pub settings: Option<CanisterSettings>,
+}
+
+// A canister created for a project, install_error is set when the optional install failed
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct CreatedCanister {
+    pub canister: Principal,
+    pub install_error: Option<ManageError>,
+}
+
 #[derive(CandidType, Debug, Deserialize)]
 pub struct CanisterStatusResponse {
     pub status: CanisterStatus,
@@ -51,13 +115,22 @@ impl ManageCanister {
             settings: settings,
         }
     }
-    pub async fn set_controller(self) -> Result<(), String> {
+    pub async fn set_controller(self) -> Result<(), ManageError> {
+        self.update_settings().await
+    }
+
+    // Settings left as None are not changed by the management canister
+    pub async fn update_settings(self) -> Result<(), ManageError> {
         match call(Principal::management_canister(), "update_settings", (self,)).await {
             Ok(()) => {
                 return Ok(());
             }
             Err((code, msg)) => {
-                return Err(format!("update canister faile: {}: {}", code as u8, msg));
+                return Err(ManageError::management_call_failed(
+                    "update_settings",
+                    code,
+                    msg,
+                ));
             }
         }
     }
@@ -65,7 +138,7 @@ impl ManageCanister {
     pub async fn get_canister_status(
         canister: Principal,
         cycle_floor: Nat,
-    ) -> Result<(CanisterStatusResponse, Nat), String> {
+    ) -> Result<(CanisterStatusResponse, Nat), ManageError> {
         let canister_id = CanisterIdRecord {
             canister_id: canister,
         };
@@ -79,12 +152,16 @@ impl ManageCanister {
         {
             Ok((status,)) => return Ok((status, cycle_floor)),
             Err((code, msg)) => {
-                return Err(msg);
+                return Err(ManageError::management_call_failed(
+                    "canister_status",
+                    code,
+                    msg,
+                ));
             }
         }
     }
 
-    pub async fn stop_canister(canister: Principal) -> Result<(), String> {
+    pub async fn stop_canister(canister: Principal) -> Result<(), ManageError> {
         let canister_id = CanisterIdRecord {
             canister_id: canister,
         };
@@ -98,15 +175,16 @@ impl ManageCanister {
         {
             Ok(()) => return Ok(()),
             Err((code, msg)) => {
-                return Err(format!(
-                    "get_canister_status faile: {}: {}",
-                    code as u8, msg
+                return Err(ManageError::management_call_failed(
+                    "stop_canister",
+                    code,
+                    msg,
                 ));
             }
         }
     }
 
-    pub async fn start_canister(canister: Principal) -> Result<(), String> {
+    pub async fn start_canister(canister: Principal) -> Result<(), ManageError> {
         let canister_id = CanisterIdRecord {
             canister_id: canister,
         };
@@ -120,15 +198,16 @@ impl ManageCanister {
         {
             Ok(()) => return Ok(()),
             Err((code, msg)) => {
-                return Err(format!(
-                    "get_canister_status faile: {}: {}",
-                    code as u8, msg
+                return Err(ManageError::management_call_failed(
+                    "start_canister",
+                    code,
+                    msg,
                 ));
             }
         }
     }
 
-    pub async fn delete_canister(canister: Principal) -> Result<(), String> {
+    pub async fn delete_canister(canister: Principal) -> Result<(), ManageError> {
         let canister_id = CanisterIdRecord {
             canister_id: canister,
         };
@@ -142,9 +221,64 @@ impl ManageCanister {
         {
             Ok(()) => return Ok(()),
             Err((code, msg)) => {
-                return Err(format!(
-                    "get_canister_status faile: {}: {}",
-                    code as u8, msg
+                return Err(ManageError::management_call_failed(
+                    "delete_canister",
+                    code,
+                    msg,
+                ));
+            }
+        }
+    }
+
+    pub async fn deposit_cycles(canister: Principal, cycles: u128) -> Result<(), ManageError> {
+        let canister_id = CanisterIdRecord {
+            canister_id: canister,
+        };
+
+        match call_with_payment128(
+            Principal::management_canister(),
+            "deposit_cycles",
+            (canister_id,),
+            cycles,
+        )
+        .await
+        {
+            Ok(()) => return Ok(()),
+            Err((code, msg)) => {
+                return Err(ManageError::management_call_failed(
+                    "deposit_cycles",
+                    code,
+                    msg,
+                ));
+            }
+        }
+    }
+
+    pub async fn create_canister(
+        settings: CanisterSettings,
+        cycles: u128,
+    ) -> Result<Principal, ManageError> {
+        let create_args = CreateCanisterArgument {
+            settings: Some(settings),
+        };
+
+        match call_with_payment128(
+            Principal::management_canister(),
+            "create_canister",
+            (create_args,),
+            cycles,
+        )
+        .await
+        {
+            Ok((canister_id,)) => {
+                let canister_id: CanisterIdRecord = canister_id;
+                return Ok(canister_id.canister_id);
+            }
+            Err((code, msg)) => {
+                return Err(ManageError::management_call_failed(
+                    "create_canister",
+                    code,
+                    msg,
                 ));
             }
         }
@@ -155,23 +289,27 @@ impl ManageCanister {
         install_mod: InstallCodeMode,
         wasm: Vec<u8>,
         args: Vec<u8>,
-    ) -> Result<(), String> {
-        let canister_id = CanisterIdRecord {
+    ) -> Result<(), ManageError> {
+        let install_args = InstallCodeArgument {
+            mode: install_mod,
             canister_id: canister,
+            wasm_module: wasm,
+            arg: args,
         };
 
         match call(
             Principal::management_canister(),
             "install_code",
-            (install_mod, canister_id, wasm, args),
+            (install_args,),
         )
         .await
         {
             Ok(()) => return Ok(()),
             Err((code, msg)) => {
-                return Err(format!(
-                    "get_canister_status faile: {}: {}",
-                    code as u8, msg
+                return Err(ManageError::management_call_failed(
+                    "install_code",
+                    code,
+                    msg,
                 ));
             }
         }
@@ -200,4 +338,54 @@ impl CanisterSettings {
             freezing_threshold: freezing_threshold,
         }
     }
+
+    // Controllers must keep the manage canister and at least one user, as in util::change_controllers
+    pub fn validate(&self, manage_canister: Principal) -> Result<(), ManageError> {
+        if let Some(controllers) = &self.controllers {
+            if controllers.is_empty() || controllers.len() > constant::MAX_CONTROLLERS {
+                return Err(ManageError::InvalidArgument(format!(
+                    "a canister needs between 1 and {} controllers",
+                    constant::MAX_CONTROLLERS
+                )));
+            }
+            if !controllers.contains(&manage_canister) {
+                return Err(ManageError::InvalidArgument(
+                    "the manage canister must remain a controller".to_string(),
+                ));
+            }
+            if !controllers.iter().any(util::is_self_authenticating) {
+                return Err(ManageError::InvalidArgument(
+                    "the canister needs at least one user controller".to_string(),
+                ));
+            }
+        }
+        let bounds = [
+            (
+                "compute_allocation",
+                &self.compute_allocation,
+                constant::MAX_COMPUTE_ALLOCATION,
+            ),
+            (
+                "memory_allocation",
+                &self.memory_allocation,
+                constant::MAX_MEMORY_ALLOCATION,
+            ),
+            (
+                "freezing_threshold",
+                &self.freezing_threshold,
+                constant::MAX_FREEZING_THRESHOLD,
+            ),
+        ];
+        for (name, value, max) in bounds {
+            if let Some(value) = value {
+                if util::nat_to_u128(value) > max as u128 {
+                    return Err(ManageError::InvalidArgument(format!(
+                        "{} must not exceed {}",
+                        name, max
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
 }
@@ -9,6 +9,8 @@ pub struct Member {
     pub identity: Principal,
     pub join_time: u64,
     pub expiration_time: Option<u64>,
+    // Name of a custom role of the group, on top of the built-in authority
+    pub role: Option<String>,
 }
 
 impl Member {
@@ -18,6 +20,7 @@ impl Member {
         identity: Principal,
         join_time: u64,
         expiration_time: Option<u64>,
+        role: Option<String>,
     ) -> Self {
         Self {
             name: name,
@@ -25,6 +28,22 @@ impl Member {
             identity: identity,
             join_time: join_time,
             expiration_time: expiration_time,
+            role: role,
+        }
+    }
+
+    // Used by every permission and visibility check, expired members act as non-members
+    pub fn is_active(&self, now: u64) -> bool {
+        match self.expiration_time {
+            None => true,
+            Some(expiration_time) => expiration_time >= now,
+        }
+    }
+
+    pub fn expires_within(&self, now: u64, window: u64) -> bool {
+        match self.expiration_time {
+            None => false,
+            Some(expiration_time) => expiration_time >= now && expiration_time <= now + window,
         }
     }
 }
@@ -0,0 +1,119 @@
+use crate::authority::Authority;
+use crate::group::Group;
+use crate::permission::Permission;
+use ic_cdk::export::candid::{CandidType, Deserialize};
+use ic_cdk::export::Principal;
+use std::collections::{HashMap, HashSet};
+
+// Membership of a principal in a group of owner, or in one of its projects when project_id is set
+#[derive(CandidType, Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Membership {
+    pub owner: Principal,
+    pub group_id: u64,
+    pub project_id: Option<u64>,
+}
+
+impl Membership {
+    pub fn new(owner: Principal, group_id: u64, project_id: Option<u64>) -> Self {
+        Self {
+            owner: owner,
+            group_id: group_id,
+            project_id: project_id,
+        }
+    }
+}
+
+// A membership resolved against its group, with the effective authority of the member
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct MembershipInfo {
+    pub owner: Principal,
+    pub group_id: u64,
+    pub group_name: String,
+    pub project_id: Option<u64>,
+    pub project_name: Option<String>,
+    pub authority: Authority,
+    pub role: Option<String>,
+    pub permissions: Vec<Permission>,
+    pub expiration_time: Option<u64>,
+}
+
+// member -> groups and projects the member belongs to, kept in sync with the members
+// of every group and project by the endpoints changing them
+#[derive(Debug, Clone, Default)]
+pub struct MembershipIndex {
+    memberships: HashMap<Principal, HashSet<Membership>>,
+}
+
+impl MembershipIndex {
+    pub fn insert(&mut self, member: Principal, membership: Membership) {
+        self.memberships.entry(member).or_default().insert(membership);
+    }
+
+    pub fn remove(&mut self, member: Principal, membership: &Membership) {
+        if let Some(memberships) = self.memberships.get_mut(&member) {
+            memberships.remove(membership);
+            if memberships.is_empty() {
+                self.memberships.remove(&member);
+            }
+        }
+    }
+
+    pub fn get(&self, member: Principal) -> Vec<Membership> {
+        match self.memberships.get(&member) {
+            None => Vec::new(),
+            Some(memberships) => memberships.iter().cloned().collect(),
+        }
+    }
+
+    // Every membership in the group and its projects
+    fn group_memberships(owner: Principal, group: &Group) -> Vec<(Principal, Membership)> {
+        let mut memberships: Vec<(Principal, Membership)> = group
+            .members
+            .keys()
+            .map(|member| (*member, Membership::new(owner, group.id, None)))
+            .collect();
+        for (project_id, project) in group.projects.iter() {
+            for member in project.members.keys() {
+                memberships.push((*member, Membership::new(owner, group.id, Some(*project_id))));
+            }
+        }
+        memberships
+    }
+
+    pub fn add_group(&mut self, owner: Principal, group: &Group) {
+        for (member, membership) in Self::group_memberships(owner, group) {
+            self.insert(member, membership);
+        }
+    }
+
+    pub fn remove_group(&mut self, owner: Principal, group: &Group) {
+        for (member, membership) in Self::group_memberships(owner, group) {
+            self.remove(member, &membership);
+        }
+    }
+
+    // Memberships follow the group when it moves to another owner
+    pub fn move_group(&mut self, from: Principal, to: Principal, group: &Group) {
+        self.remove_group(from, group);
+        self.add_group(to, group);
+    }
+
+    pub fn to_vec(&self) -> Vec<(Principal, Membership)> {
+        self.memberships
+            .iter()
+            .flat_map(|(member, memberships)| {
+                memberships
+                    .iter()
+                    .map(move |membership| (*member, *membership))
+            })
+            .collect()
+    }
+
+    pub fn from_vec(memberships: Vec<(Principal, Membership)>) -> Self {
+        let mut index = Self::default();
+        for (member, membership) in memberships {
+            index.insert(member, membership);
+        }
+        index
+    }
+}
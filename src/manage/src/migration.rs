@@ -0,0 +1,114 @@
+use crate::group::Group;
+use crate::member::Member;
+use crate::project::{Project, ProjectType};
+use crate::types::Profile;
+use crate::user::User;
+use ic_cdk::export::candid::{CandidType, Deserialize, Nat};
+use ic_cdk::export::Principal;
+use std::collections::HashMap;
+
+// Records as saved to stable memory by the first release, which stored only
+// (owner, log canister, users). Fields added since then are left at their defaults
+// when the records are migrated, fields removed since then are ignored when decoding
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct LegacyUser {
+    pub user_name: String,
+    pub profile: Profile,
+    pub identity: Principal,
+    pub groups: HashMap<u64, LegacyGroup>,
+    pub create_time: u64,
+}
+
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct LegacyGroup {
+    pub id: u64,
+    pub create_time: u64,
+    pub visibility: Profile,
+    pub name: String,
+    pub description: String,
+    pub projects: HashMap<u64, LegacyProject>,
+    pub members: HashMap<Principal, Member>,
+    pub url: String,
+}
+
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct LegacyProject {
+    pub id: u64,
+    pub create_time: u64,
+    pub in_group: u64,
+    pub visibility: Profile,
+    pub create_by: Principal,
+    pub name: String,
+    pub description: String,
+    pub git_repo_url: String,
+    pub members: HashMap<Principal, Member>,
+    pub canister_cycle_floor: Nat,
+    pub canisters: Vec<Principal>,
+    pub function: ProjectType,
+}
+
+impl From<LegacyProject> for Project {
+    fn from(project: LegacyProject) -> Self {
+        Self {
+            id: project.id,
+            create_time: project.create_time,
+            in_group: project.in_group,
+            visibility: project.visibility,
+            create_by: project.create_by,
+            name: project.name,
+            description: project.description,
+            git_repo_url: project.git_repo_url,
+            members: project.members,
+            canister_cycle_floor: project.canister_cycle_floor,
+            canisters: project.canisters,
+            function: project.function,
+            installed_modules: HashMap::new(),
+            controllability: HashMap::new(),
+            canister_acls: HashMap::new(),
+        }
+    }
+}
+
+// The owner is set by the user holding the group
+impl From<LegacyGroup> for Group {
+    fn from(group: LegacyGroup) -> Self {
+        Self {
+            id: group.id,
+            create_time: group.create_time,
+            visibility: group.visibility,
+            name: group.name,
+            description: group.description,
+            projects: group
+                .projects
+                .into_iter()
+                .map(|(id, project)| (id, project.into()))
+                .collect(),
+            members: group.members,
+            url: group.url,
+            roles: HashMap::new(),
+            owner: None,
+        }
+    }
+}
+
+impl From<LegacyUser> for User {
+    fn from(user: LegacyUser) -> Self {
+        let mut migrated = User::new(
+            user.user_name,
+            user.profile,
+            user.identity,
+            user.create_time,
+        );
+        let owner = user.identity;
+        migrated.groups = user
+            .groups
+            .into_iter()
+            .map(|(id, group)| {
+                let mut group: Group = group.into();
+                group.owner = Some(owner);
+                (id, group)
+            })
+            .collect();
+        migrated
+    }
+}
@@ -0,0 +1,37 @@
+use crate::constant;
+use crate::manage::{CanisterStatus, CanisterStatusResponse};
+use ic_cdk::export::candid::Nat;
+use ic_cdk::export::candid::{CandidType, Deserialize};
+
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct StatusSnapshot {
+    pub time: u64,
+    pub status: CanisterStatus,
+    pub memory_size: Nat,
+    pub cycles: Nat,
+    pub module_hash: Option<Vec<u8>>,
+    pub freezing_threshold: Option<Nat>,
+}
+
+impl StatusSnapshot {
+    pub fn new(time: u64, status: &CanisterStatusResponse) -> Self {
+        Self {
+            time: time,
+            status: status.status.clone(),
+            memory_size: status.memory_size.clone(),
+            cycles: status.cycles.clone(),
+            module_hash: status.module_hash.clone(),
+            freezing_threshold: status.settings.freezing_threshold.clone(),
+        }
+    }
+}
+
+// Snapshots of one canister in chronological order, the oldest ones are dropped
+// once the history exceeds STATUS_HISTORY_LIMIT
+pub fn push_snapshot(history: &mut Vec<StatusSnapshot>, snapshot: StatusSnapshot) {
+    history.push(snapshot);
+    if history.len() > constant::STATUS_HISTORY_LIMIT {
+        let overflow = history.len() - constant::STATUS_HISTORY_LIMIT;
+        history.drain(..overflow);
+    }
+}
@@ -0,0 +1,24 @@
+use crate::member::Member;
+use ic_cdk::export::candid::{CandidType, Deserialize};
+use ic_cdk::export::Principal;
+
+// Member of a group, or of one of its projects when project_id is set, close to its expiration.
+// Also the argument of `notify_member_expiry` on the notification canister
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct ExpiringMember {
+    pub account: Principal,
+    pub group_id: u64,
+    pub project_id: Option<u64>,
+    pub member: Member,
+}
+
+impl ExpiringMember {
+    pub fn new(account: Principal, group_id: u64, project_id: Option<u64>, member: Member) -> Self {
+        Self {
+            account: account,
+            group_id: group_id,
+            project_id: project_id,
+            member: member,
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use ic_cdk::export::candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+// A single capability on a group or project, roles are sets of permissions
+#[derive(CandidType, Debug, Deserialize, Clone, Serialize, Copy, PartialEq, Eq, Hash)]
+pub enum Permission {
+    // Read the status, cycles and history of the project and its canisters
+    ViewStatus,
+    // Modify the name, description, visibility and git url
+    EditMetadata,
+    // Add, create and remove canisters of a project
+    ManageCanisters,
+    // Set the cycle floor and top-up policy
+    TopUp,
+    StartStop,
+    // Upload wasm, publish modules, install code and run rollouts
+    InstallCode,
+    Delete,
+    ManageMembers,
+    // Update canister settings and controllers
+    ManageSettings,
+    // Add and remove projects of a group
+    ManageProjects,
+    // Create, edit and delete the custom roles of a group
+    ManageRoles,
+}
@@ -1,6 +1,11 @@
 use crate::authority::Authority;
-use crate::manage::{CanisterSettings, CanisterStatusResponse, InstallCodeMode, ManageCanister};
+use crate::error::{ManageError, ResourceKind};
+use crate::manage::{
+    CanisterSettings, CanisterStatusResponse, Controllability, InstallCodeMode, ManageCanister,
+};
 use crate::member::Member;
+use crate::permission::Permission;
+use crate::registry::InstalledModule;
 use crate::types::Profile;
 
 use ic_cdk::api::caller;
@@ -41,6 +46,12 @@ pub struct Project {
     pub canister_cycle_floor: Nat,
     pub canisters: Vec<Principal>,
     pub function: ProjectType,
+    // Registry module version installed on each canister, maintained by the manage canister
+    pub installed_modules: HashMap<Principal, InstalledModule>,
+    // Controllers of each canister as seen by the manage canister, maintained by the manage canister
+    pub controllability: HashMap<Principal, Controllability>,
+    // Permissions of a principal on a single canister, they replace its project permissions there
+    pub canister_acls: HashMap<Principal, HashMap<Principal, Vec<Permission>>>,
 }
 
 impl Project {
@@ -78,103 +89,97 @@ impl Project {
             canister_cycle_floor: canister_cycle_floor,
             canisters: canisters.to_owned(),
             function: function,
+            installed_modules: HashMap::new(),
+            controllability: HashMap::new(),
+            canister_acls: HashMap::new(),
         }
     }
 
-    fn identity_check(&self, opt: Authority, sender: Principal) -> Result<(), String> {
-        match self.members.get(&sender) {
-            None => {
-                return Err("not in the group member list".to_string());
-            }
-            Some(member) => {
-                if !Authority::authority_check(member.authority.clone(), opt.clone()) {
-                    return Err(format!("project permission verification failed: user permissions: {:?},opt permissions: {:?}",member.authority.clone(),opt));
-                }
-                if let Some(expir) = member.expiration_time {
-                    if expir < ic_cdk::api::time() {
-                        return Err("Identity expiration".to_string());
-                    }
-                }
-
-                Ok(())
-            }
-        }
-    }
-
-    pub fn add_member(&mut self, member: Member) -> Result<(), String> {
+    // Permissions are checked by the group holding the project, see Group::project_identity_check
+    pub fn add_member(&mut self, member: Member) -> Result<(), ManageError> {
         self.members.insert(member.identity, member);
         Ok(())
     }
 
-    pub fn remove_member(&mut self, member: Principal) -> Result<(), String> {
+    pub fn remove_member(&mut self, member: Principal) -> Result<(), ManageError> {
         self.members.remove(&member);
+        self.remove_canister_acls(member);
         Ok(())
     }
 
-    pub fn update_git_repo_url(&mut self, git: &str, sender: Principal) -> Result<(), String> {
-        self.identity_check(Authority::Write, sender)?;
+    // Drops every canister acl entry of principal
+    pub fn remove_canister_acls(&mut self, principal: Principal) {
+        for acl in self.canister_acls.values_mut() {
+            acl.remove(&principal);
+        }
+        self.canister_acls.retain(|_, acl| !acl.is_empty());
+    }
+
+    pub fn update_git_repo_url(&mut self, git: &str) -> Result<(), ManageError> {
         self.git_repo_url = git.to_string();
         Ok(())
     }
-    pub fn update_canister_cycle_floor(
-        &mut self,
-        floor: Nat,
-        sender: Principal,
-    ) -> Result<(), String> {
-        self.identity_check(Authority::Write, sender)?;
+    pub fn update_canister_cycle_floor(&mut self, floor: Nat) -> Result<(), ManageError> {
         self.canister_cycle_floor = floor;
         Ok(())
     }
 
-    pub fn update_visibility(
-        &mut self,
-        visibility: Profile,
-        sender: Principal,
-    ) -> Result<(), String> {
-        self.identity_check(Authority::Write, sender)?;
+    pub fn update_visibility(&mut self, visibility: Profile) -> Result<(), ManageError> {
         self.visibility = visibility;
         Ok(())
     }
 
-    pub fn update_description(
-        &mut self,
-        description: &str,
-        sender: Principal,
-    ) -> Result<(), String> {
-        self.identity_check(Authority::Write, sender)?;
+    pub fn update_description(&mut self, description: &str) -> Result<(), ManageError> {
         self.description = description.to_string();
         Ok(())
     }
 
-    pub fn add_canister(&mut self, canister: Principal, sender: Principal) -> Result<(), String> {
-        self.identity_check(Authority::Write, sender)?;
+    pub fn add_canister(&mut self, canister: Principal) -> Result<(), ManageError> {
         if self.canisters.contains(&canister) {
-            return Err("canisters already exist".to_string());
+            return Err(ManageError::already_exists(
+                ResourceKind::Canister,
+                canister,
+            ));
         }
         self.canisters.push(canister);
         Ok(())
     }
 
-    pub fn remove_canister(
-        &mut self,
-        canister: Principal,
-        sender: Principal,
-    ) -> Result<(), String> {
-        self.identity_check(Authority::Write, sender)?;
-        if self.canisters.contains(&canister) {
-            return Err("canisters do not exist".to_string());
+    pub fn remove_canister(&mut self, canister: Principal) -> Result<(), ManageError> {
+        if !self.canisters.contains(&canister) {
+            return Err(ManageError::not_found(ResourceKind::Canister, canister));
         }
         self.canisters.retain(|&x| x != canister);
+        self.installed_modules.remove(&canister);
+        self.controllability.remove(&canister);
+        self.canister_acls.remove(&canister);
         Ok(())
     }
 
+    pub fn set_installed_module(&mut self, canister: Principal, module: Option<InstalledModule>) {
+        match module {
+            None => {
+                self.installed_modules.remove(&canister);
+            }
+            Some(module) => {
+                self.installed_modules.insert(canister, module);
+            }
+        }
+    }
+
+    pub fn set_controllability(&mut self, canister: Principal, controllability: Controllability) {
+        if self.canisters.contains(&canister) {
+            self.controllability.insert(canister, controllability);
+        }
+    }
+
     pub fn update_member_authority(
         &mut self,
         member: Principal,
         authority: Authority,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
         match self.members.get_mut(&member) {
-            None => Err("member is not in the project".to_string()),
+            None => Err(ManageError::not_found(ResourceKind::Member, member)),
             Some(member) => {
                 member.authority = authority;
                 return Ok(());
@@ -182,31 +187,74 @@ impl Project {
         }
     }
 
+    pub fn update_member_expiration(
+        &mut self,
+        member: Principal,
+        expiration_time: Option<u64>,
+    ) -> Result<(), ManageError> {
+        match self.members.get_mut(&member) {
+            None => Err(ManageError::not_found(ResourceKind::Member, member)),
+            Some(member) => {
+                member.expiration_time = expiration_time;
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn update_canister_acl(
+        &mut self,
+        canister: Principal,
+        principal: Principal,
+        permissions: Option<Vec<Permission>>,
+    ) -> Result<(), ManageError> {
+        if !self.canisters.contains(&canister) {
+            return Err(ManageError::not_found(ResourceKind::Canister, canister));
+        }
+        let acl = self.canister_acls.entry(canister).or_default();
+        match permissions {
+            None => {
+                acl.remove(&principal);
+            }
+            Some(permissions) => {
+                acl.insert(principal, permissions);
+            }
+        }
+        if acl.is_empty() {
+            self.canister_acls.remove(&canister);
+        }
+        Ok(())
+    }
+
+    pub fn update_member_role(
+        &mut self,
+        member: Principal,
+        role: Option<String>,
+    ) -> Result<(), ManageError> {
+        match self.members.get_mut(&member) {
+            None => Err(ManageError::not_found(ResourceKind::Member, member)),
+            Some(member) => {
+                member.role = role;
+                return Ok(());
+            }
+        }
+    }
+
     pub fn get_canister_status(
         &self,
         canister: Principal,
-        sender: Principal,
-        check: bool,
-    ) -> Result<impl Future<Output = Result<(CanisterStatusResponse, Nat), String>>, String> {
+    ) -> Result<impl Future<Output = Result<(CanisterStatusResponse, Nat), ManageError>>, ManageError>
+    {
         if self.canisters.contains(&canister) {
-            if check {
-                self.identity_check(Authority::Read, sender)?;
-            }
             let canister_cycle_floor = self.canister_cycle_floor.clone();
             return Ok(async move {
                 ManageCanister::get_canister_status(canister, canister_cycle_floor).await
             });
         }
-        return Err("canisters do not exist in the project".to_string());
+        return Err(ManageError::not_found(ResourceKind::Canister, canister));
     }
 
-    pub async fn set_canister_controller(
-        &self,
-        canister: Principal,
-        sender: Principal,
-    ) -> Result<(), String> {
+    pub async fn set_canister_controller(&self, canister: Principal) -> Result<(), ManageError> {
         if self.canisters.contains(&canister) {
-            self.identity_check(Authority::Operational, sender)?;
             let controllers: Option<Vec<Principal>> = Some(vec![ic_cdk::api::caller()]);
             let compute_allocation: Nat = "0".parse().unwrap();
             let memory_allocation: Nat = "0".parse().unwrap();
@@ -220,43 +268,37 @@ impl Project {
             let mange_canister = ManageCanister::new(canister, canister_settings);
             return mange_canister.set_controller().await;
         }
-        return Err("canisters do not exist in the project".to_string());
+        return Err(ManageError::not_found(ResourceKind::Canister, canister));
     }
 
     pub fn stop_canister(
         &self,
         canister: Principal,
-        sender: Principal,
-    ) -> Result<impl Future<Output = Result<(), String>>, String> {
+    ) -> Result<impl Future<Output = Result<(), ManageError>>, ManageError> {
         if self.canisters.contains(&canister) {
-            self.identity_check(Authority::Operational, sender)?;
             return Ok(async move { ManageCanister::stop_canister(canister).await });
         }
-        return Err("canisters do not exist in the project".to_string());
+        return Err(ManageError::not_found(ResourceKind::Canister, canister));
     }
 
     pub fn start_canister(
         &self,
         canister: Principal,
-        sender: Principal,
-    ) -> Result<impl Future<Output = Result<(), String>>, String> {
+    ) -> Result<impl Future<Output = Result<(), ManageError>>, ManageError> {
         if self.canisters.contains(&canister) {
-            self.identity_check(Authority::Operational, sender)?;
             return Ok(async move { ManageCanister::start_canister(canister).await });
         }
-        return Err("canisters do not exist in the project".to_string());
+        return Err(ManageError::not_found(ResourceKind::Canister, canister));
     }
 
     pub fn delete_canister(
         &self,
         canister: Principal,
-        sender: Principal,
-    ) -> Result<impl Future<Output = Result<(), String>>, String> {
+    ) -> Result<impl Future<Output = Result<(), ManageError>>, ManageError> {
         if self.canisters.contains(&canister) {
-            self.identity_check(Authority::Operational, sender)?;
             return Ok(async move { ManageCanister::delete_canister(canister).await });
         }
-        return Err("canisters do not exist in the project".to_string());
+        return Err(ManageError::not_found(ResourceKind::Canister, canister));
     }
 
     pub fn install_code(
@@ -265,37 +307,43 @@ impl Project {
         install_mod: InstallCodeMode,
         wasm: Vec<u8>,
         args: Vec<u8>,
-        sender: Principal,
-    ) -> Result<impl Future<Output = Result<(), String>>, String> {
+    ) -> Result<impl Future<Output = Result<(), ManageError>>, ManageError> {
         if self.canisters.contains(&canister) {
-            self.identity_check(Authority::Operational, sender)?;
             return Ok(async move {
                 ManageCanister::install_code(canister, install_mod, wasm, args).await
             });
         }
-        return Err("canisters do not exist in the project".to_string());
+        return Err(ManageError::not_found(ResourceKind::Canister, canister));
+    }
+
+    pub fn update_canister_settings(
+        &self,
+        canister: Principal,
+        settings: CanisterSettings,
+    ) -> Result<impl Future<Output = Result<(), ManageError>>, ManageError> {
+        if self.canisters.contains(&canister) {
+            settings.validate(ic_cdk::api::id())?;
+            return Ok(async move {
+                ManageCanister::new(canister, settings)
+                    .update_settings()
+                    .await
+            });
+        }
+        return Err(ManageError::not_found(ResourceKind::Canister, canister));
     }
 
+    // Canisters and the cycle floor are changed through their own endpoints and permissions
     pub fn update_basic_information(
         &mut self,
         name: String,
         description: String,
         visibility: Profile,
         git: String,
-        canister_cycle_floor: Nat,
-        canisters: &[Principal],
-        sender: Principal,
-        check: bool,
-    ) -> Result<(), String> {
-        if check {
-            self.identity_check(Authority::Write, sender)?;
-        }
+    ) -> Result<(), ManageError> {
         self.name = name;
         self.description = description;
         self.visibility = visibility;
         self.git_repo_url = git;
-        self.canister_cycle_floor = canister_cycle_floor;
-        self.canisters = canisters.to_owned();
         Ok(())
     }
 }
@@ -0,0 +1,150 @@
+use crate::constant;
+use crate::error::{ManageError, ResourceKind};
+use crate::manage::InstallCodeMode;
+use ic_cdk::export::candid::{CandidType, Deserialize};
+use ic_cdk::export::Principal;
+
+// A named and versioned Wasm module published to a project,
+// the module itself is stored once under its sha256
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct WasmModule {
+    pub name: String,
+    pub version: String,
+    pub sha256: Vec<u8>,
+    pub size: u64,
+    pub uploader: Principal,
+    pub release_notes: String,
+    pub create_time: u64,
+}
+
+// Module information without the module itself
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct ModuleInfo {
+    pub name: String,
+    pub version: String,
+    pub sha256: Vec<u8>,
+    pub size: u64,
+    pub uploader: Principal,
+    pub release_notes: String,
+    pub create_time: u64,
+}
+
+// The registry version installed on a project canister
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct InstalledModule {
+    pub name: String,
+    pub version: String,
+    pub sha256: Vec<u8>,
+    pub install_time: u64,
+}
+
+// A registry module version to install on a project canister
+pub struct ModuleInstall {
+    pub name: String,
+    pub version: String,
+    pub mode: InstallCodeMode,
+    pub args: Vec<u8>,
+}
+
+impl ModuleInstall {
+    pub fn new(name: &str, version: &str, mode: InstallCodeMode, args: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            version: version.to_string(),
+            mode: mode,
+            args: args,
+        }
+    }
+}
+
+impl WasmModule {
+    pub fn new(
+        name: String,
+        version: String,
+        sha256: Vec<u8>,
+        uploader: Principal,
+        release_notes: String,
+        create_time: u64,
+        size: u64,
+    ) -> Self {
+        Self {
+            name: name,
+            version: version,
+            sha256: sha256,
+            size: size,
+            uploader: uploader,
+            release_notes: release_notes,
+            create_time: create_time,
+        }
+    }
+
+    pub fn info(&self) -> ModuleInfo {
+        ModuleInfo {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            sha256: self.sha256.clone(),
+            size: self.size,
+            uploader: self.uploader,
+            release_notes: self.release_notes.clone(),
+            create_time: self.create_time,
+        }
+    }
+
+    pub fn installed(&self, install_time: u64) -> InstalledModule {
+        InstalledModule {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            sha256: self.sha256.clone(),
+            install_time: install_time,
+        }
+    }
+}
+
+// The modules published to one project
+#[derive(CandidType, Debug, Deserialize, Clone, Default)]
+pub struct ModuleRegistry {
+    pub modules: Vec<WasmModule>,
+}
+
+impl ModuleRegistry {
+    pub fn publish_check(&self, name: &str, version: &str) -> Result<(), ManageError> {
+        if self.get(name, version).is_some() {
+            return Err(ManageError::already_exists(
+                ResourceKind::Module,
+                format!("{}@{}", name, version),
+            ));
+        }
+        if self.modules.len() >= constant::REGISTRY_VERSION_LIMIT {
+            return Err(ManageError::InvalidState(format!(
+                "registry already has {} versions, delete one first",
+                constant::REGISTRY_VERSION_LIMIT
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn publish(&mut self, module: WasmModule) -> Result<(), ManageError> {
+        self.publish_check(&module.name, &module.version)?;
+        self.modules.push(module);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str, version: &str) -> Option<&WasmModule> {
+        self.modules
+            .iter()
+            .find(|module| module.name == name && module.version == version)
+    }
+
+    pub fn remove(&mut self, name: &str, version: &str) -> Result<(), ManageError> {
+        let len = self.modules.len();
+        self.modules
+            .retain(|module| module.name != name || module.version != version);
+        if self.modules.len() == len {
+            return Err(ManageError::not_found(
+                ResourceKind::Module,
+                format!("{}@{}", name, version),
+            ));
+        }
+        Ok(())
+    }
+}
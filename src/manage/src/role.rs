@@ -0,0 +1,21 @@
+use crate::permission::Permission;
+use ic_cdk::export::candid::{CandidType, Deserialize};
+
+// A named set of permissions defined by a group, such as "release-manager" or "auditor".
+// Members get the permissions of their role on top of the ones of their built-in authority
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct Role {
+    pub name: String,
+    pub description: String,
+    pub permissions: Vec<Permission>,
+}
+
+impl Role {
+    pub fn new(name: String, description: String, permissions: Vec<Permission>) -> Self {
+        Self {
+            name: name,
+            description: description,
+            permissions: permissions,
+        }
+    }
+}
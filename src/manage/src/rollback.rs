@@ -0,0 +1,78 @@
+use crate::constant;
+use crate::error::ManageError;
+use crate::registry::InstalledModule;
+use ic_cdk::export::candid::{CandidType, Deserialize};
+use ic_cdk::export::Principal;
+use sha2::{Digest, Sha256};
+
+// A module installed on a canister through the manage canister, kept to be able to roll back to it.
+// The module itself is stored once under its sha256
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct InstallRecord {
+    pub args: Vec<u8>,
+    pub sha256: Vec<u8>,
+    pub size: u64,
+    pub installer: Principal,
+    pub install_time: u64,
+    // Set when the module was installed from the project's module registry
+    pub module: Option<InstalledModule>,
+}
+
+// Install record information without the module itself
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct InstallRecordInfo {
+    pub sha256: Vec<u8>,
+    pub size: u64,
+    pub installer: Principal,
+    pub install_time: u64,
+    pub module: Option<InstalledModule>,
+}
+
+impl InstallRecord {
+    pub fn new(
+        wasm: &[u8],
+        args: Vec<u8>,
+        installer: Principal,
+        install_time: u64,
+        module: Option<InstalledModule>,
+    ) -> Self {
+        let sha256 = Sha256::digest(wasm).to_vec();
+        Self {
+            args: args,
+            sha256: sha256,
+            size: wasm.len() as u64,
+            installer: installer,
+            install_time: install_time,
+            module: module,
+        }
+    }
+
+    pub fn info(&self) -> InstallRecordInfo {
+        InstallRecordInfo {
+            sha256: self.sha256.clone(),
+            size: self.size,
+            installer: self.installer,
+            install_time: self.install_time,
+            module: self.module.clone(),
+        }
+    }
+}
+
+// Install records of one canister in chronological order, the last one is the installed module
+pub fn push_record(history: &mut Vec<InstallRecord>, record: InstallRecord) {
+    history.push(record);
+    if history.len() > constant::INSTALL_HISTORY_LIMIT {
+        let overflow = history.len() - constant::INSTALL_HISTORY_LIMIT;
+        history.drain(..overflow);
+    }
+}
+
+// The module installed before the current one
+pub fn previous_record(history: &[InstallRecord]) -> Result<&InstallRecord, ManageError> {
+    if history.len() < 2 {
+        return Err(ManageError::InvalidState(
+            "no previous module to roll back to".to_string(),
+        ));
+    }
+    Ok(&history[history.len() - 2])
+}
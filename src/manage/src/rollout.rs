@@ -0,0 +1,172 @@
+use crate::error::ManageError;
+use ic_cdk::export::candid::{CandidType, Deserialize};
+use ic_cdk::export::Principal;
+
+#[derive(CandidType, Debug, Deserialize, Clone, PartialEq, Eq)]
+pub enum RolloutStatus {
+    InProgress,
+    // An install or a status check failed, the rollout can be resumed or rolled back
+    Halted,
+    Completed,
+    RolledBack,
+    // The rollback failed on some canisters, it can only be retried
+    RollbackHalted,
+}
+
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub enum StepStatus {
+    Pending,
+    Succeeded,
+    Failed(ManageError),
+    // The module was installed but the canister failed the check afterwards
+    CheckFailed(ManageError),
+    RolledBack,
+    RollbackFailed(ManageError),
+}
+
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct RolloutStep {
+    pub canister: Principal,
+    pub status: StepStatus,
+    pub update_time: u64,
+}
+
+// Upgrade of several project canisters to one registry module version, wave by wave.
+// The canisters of a wave are upgraded together and the next wave only starts
+// once every canister of the previous wave runs the expected module
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct Rollout {
+    pub id: u64,
+    pub account: Principal,
+    pub group_id: u64,
+    pub project_id: u64,
+    pub creator: Principal,
+    pub create_time: u64,
+    pub module_name: String,
+    pub module_version: String,
+    pub args: Vec<u8>,
+    pub waves: Vec<Vec<RolloutStep>>,
+    pub status: RolloutStatus,
+}
+
+impl Rollout {
+    // Every field of a rollout but its status is given when it starts
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        id: u64,
+        account: Principal,
+        group_id: u64,
+        project_id: u64,
+        creator: Principal,
+        create_time: u64,
+        module_name: String,
+        module_version: String,
+        args: Vec<u8>,
+        waves: Vec<Vec<Principal>>,
+    ) -> Self {
+        let waves = waves
+            .into_iter()
+            .map(|wave| {
+                wave.into_iter()
+                    .map(|canister| RolloutStep {
+                        canister: canister,
+                        status: StepStatus::Pending,
+                        update_time: create_time,
+                    })
+                    .collect()
+            })
+            .collect();
+        Self {
+            id: id,
+            account: account,
+            group_id: group_id,
+            project_id: project_id,
+            creator: creator,
+            create_time: create_time,
+            module_name: module_name,
+            module_version: module_version,
+            args: args,
+            waves: waves,
+            status: RolloutStatus::InProgress,
+        }
+    }
+
+    pub fn canisters(&self) -> Vec<Principal> {
+        self.waves
+            .iter()
+            .flat_map(|wave| wave.iter().map(|step| step.canister))
+            .collect()
+    }
+
+    // The first wave that still has canisters to upgrade, with those canisters
+    pub fn next_wave(&self) -> Option<(usize, Vec<Principal>)> {
+        self.waves.iter().enumerate().find_map(|(index, wave)| {
+            let canisters: Vec<Principal> = wave
+                .iter()
+                .filter(|step| !matches!(step.status, StepStatus::Succeeded))
+                .map(|step| step.canister)
+                .collect();
+            if canisters.is_empty() {
+                return None;
+            }
+            Some((index, canisters))
+        })
+    }
+
+    // Canisters the rollout module was installed on that are not rolled back yet, last wave first
+    pub fn rollback_canisters(&self) -> Vec<Principal> {
+        self.waves
+            .iter()
+            .rev()
+            .flat_map(|wave| {
+                wave.iter()
+                    .filter(|step| {
+                        matches!(
+                            step.status,
+                            StepStatus::Succeeded
+                                | StepStatus::CheckFailed(_)
+                                | StepStatus::RollbackFailed(_)
+                        )
+                    })
+                    .map(|step| step.canister)
+            })
+            .collect()
+    }
+
+    pub fn update_step(&mut self, canister: Principal, status: StepStatus, time: u64) {
+        for wave in self.waves.iter_mut() {
+            for step in wave.iter_mut() {
+                if step.canister == canister {
+                    step.status = status.clone();
+                    step.update_time = time;
+                }
+            }
+        }
+    }
+
+    pub fn resume(&mut self) -> Result<(), ManageError> {
+        match self.status {
+            RolloutStatus::Halted => {
+                self.status = RolloutStatus::InProgress;
+                Ok(())
+            }
+            _ => Err(ManageError::InvalidState(format!(
+                "rollout cannot be resumed: {:?}",
+                self.status
+            ))),
+        }
+    }
+
+    pub fn begin_rollback(&mut self) -> Result<(), ManageError> {
+        match self.status {
+            RolloutStatus::Halted | RolloutStatus::Completed | RolloutStatus::RollbackHalted => {
+                self.status = RolloutStatus::InProgress;
+                Ok(())
+            }
+            _ => Err(ManageError::InvalidState(format!(
+                "rollout cannot be rolled back: {:?}",
+                self.status
+            ))),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::error::{ManageError, ResourceKind};
+use crate::group::Group;
+use crate::user::User;
+use ic_cdk::export::Principal;
+use std::collections::{HashMap, HashSet};
+
+// Unit of work over the user storage. Changes are staged per group of a user, so that an
+// endpoint only copies the groups it changes, and written back together with the changes
+// registered for the other storages once every check and change of the endpoint succeeded,
+// so an error half way leaves every storage unchanged
+pub struct UserTransaction<'a> {
+    storage: &'a HashMap<Principal, User>,
+    // New users, and the staged groups of existing users held by a copy of the user without
+    // its other groups
+    staged: HashMap<Principal, User>,
+    new_users: HashSet<Principal>,
+    // (account, group_id) of the staged groups, a staged group missing from its user is removed
+    groups: HashSet<(Principal, u64)>,
+    commits: Vec<Box<dyn FnOnce()>>,
+}
+
+// The changes of a successful unit of work
+pub struct Staged {
+    staged: HashMap<Principal, User>,
+    new_users: HashSet<Principal>,
+    groups: HashSet<(Principal, u64)>,
+    commits: Vec<Box<dyn FnOnce()>>,
+}
+
+impl<'a> UserTransaction<'a> {
+    pub fn new(storage: &'a HashMap<Principal, User>) -> Self {
+        Self {
+            storage: storage,
+            staged: HashMap::new(),
+            new_users: HashSet::new(),
+            groups: HashSet::new(),
+            commits: Vec::new(),
+        }
+    }
+
+    pub fn contains(&self, account: Principal) -> bool {
+        self.new_users.contains(&account) || self.storage.contains_key(&account)
+    }
+
+    pub fn insert(&mut self, account: Principal, user: User) {
+        self.new_users.insert(account);
+        self.staged.insert(account, user);
+    }
+
+    // Every (account, group_id) of the storage
+    pub fn group_keys(&self) -> Vec<(Principal, u64)> {
+        self.storage
+            .iter()
+            .flat_map(|(account, user)| {
+                user.groups
+                    .keys()
+                    .map(move |group_id| (*account, *group_id))
+            })
+            .collect()
+    }
+
+    // The group with its staged changes, None when account has no such group
+    pub fn group(&self, account: Principal, group_id: u64) -> Result<Option<&Group>, ManageError> {
+        if self.new_users.contains(&account) || self.groups.contains(&(account, group_id)) {
+            return Ok(self.staged[&account].groups.get(&group_id));
+        }
+        match self.storage.get(&account) {
+            None => Err(ManageError::not_found(ResourceKind::User, account)),
+            Some(user) => Ok(user.groups.get(&group_id)),
+        }
+    }
+
+    // The user to change group_id of account through, it only holds the staged groups
+    // of account so it must not be used to change any other group
+    pub fn user_mut(
+        &mut self,
+        account: Principal,
+        group_id: u64,
+    ) -> Result<&mut User, ManageError> {
+        if !self.new_users.contains(&account) && !self.groups.contains(&(account, group_id)) {
+            let user = match self.storage.get(&account) {
+                None => return Err(ManageError::not_found(ResourceKind::User, account)),
+                Some(user) => user,
+            };
+            let staged = self.staged.entry(account).or_insert_with(|| {
+                User::new(
+                    user.user_name.clone(),
+                    user.profile.clone(),
+                    user.identity,
+                    user.create_time,
+                )
+            });
+            if let Some(group) = user.groups.get(&group_id) {
+                staged.groups.insert(group_id, group.clone());
+            }
+            self.groups.insert((account, group_id));
+        }
+        Ok(self.staged.get_mut(&account).unwrap())
+    }
+
+    pub fn group_mut(
+        &mut self,
+        account: Principal,
+        group_id: u64,
+    ) -> Result<&mut Group, ManageError> {
+        match self.user_mut(account, group_id)?.groups.get_mut(&group_id) {
+            None => Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => Ok(group),
+        }
+    }
+
+    // Runs f on commit, used to change the other storages in the same unit of work
+    pub fn on_commit<F: FnOnce() + 'static>(&mut self, f: F) {
+        self.commits.push(Box::new(f));
+    }
+
+    pub fn into_staged(self) -> Staged {
+        Staged {
+            staged: self.staged,
+            new_users: self.new_users,
+            groups: self.groups,
+            commits: self.commits,
+        }
+    }
+}
+
+impl Staged {
+    // Writes the staged users and groups back and returns the changes of the other storages
+    pub fn apply(mut self, storage: &mut HashMap<Principal, User>) -> Vec<Box<dyn FnOnce()>> {
+        for account in self.new_users.iter() {
+            storage.insert(*account, self.staged.remove(account).unwrap());
+        }
+        for (account, group_id) in self.groups.iter() {
+            let group = self
+                .staged
+                .get_mut(account)
+                .and_then(|user| user.groups.remove(group_id));
+            if let Some(user) = storage.get_mut(account) {
+                match group {
+                    None => {
+                        user.groups.remove(group_id);
+                    }
+                    Some(group) => {
+                        user.groups.insert(*group_id, group);
+                    }
+                }
+            }
+        }
+        self.commits
+    }
+}
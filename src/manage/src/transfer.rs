@@ -0,0 +1,23 @@
+use ic_cdk::export::candid::{CandidType, Deserialize};
+use ic_cdk::export::Principal;
+
+// Offer of the owner of a group to move it, with all its projects, under another user.
+// The group only moves when the recipient accepts the offer
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct GroupTransfer {
+    pub from: Principal,
+    pub group_id: u64,
+    pub to: Principal,
+    pub offer_time: u64,
+}
+
+impl GroupTransfer {
+    pub fn new(from: Principal, group_id: u64, to: Principal, offer_time: u64) -> Self {
+        Self {
+            from: from,
+            group_id: group_id,
+            to: to,
+            offer_time: offer_time,
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use crate::constant;
+use crate::error::{ManageError, ResourceKind};
+use ic_cdk::export::candid::{CandidType, Deserialize};
+use ic_cdk::export::Principal;
+use sha2::{Digest, Sha256};
+
+// A Wasm module uploaded in chunks, so that modules close to the ingress message limit
+// can still be installed through the manage canister
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct UploadSession {
+    pub id: u64,
+    pub uploader: Principal,
+    pub account: Principal,
+    pub group_id: u64,
+    pub project_id: u64,
+    pub create_time: u64,
+    pub wasm: Vec<u8>,
+    // Set once the upload is finalized and the SHA-256 of the module has been verified
+    pub sha256: Option<Vec<u8>>,
+}
+
+// Upload information without the module itself
+#[derive(CandidType, Debug, Deserialize, Clone)]
+pub struct UploadInfo {
+    pub id: u64,
+    pub uploader: Principal,
+    pub create_time: u64,
+    pub size: u64,
+    pub sha256: Option<Vec<u8>>,
+}
+
+impl UploadSession {
+    pub fn new(
+        id: u64,
+        uploader: Principal,
+        account: Principal,
+        group_id: u64,
+        project_id: u64,
+        create_time: u64,
+    ) -> Self {
+        Self {
+            id: id,
+            uploader: uploader,
+            account: account,
+            group_id: group_id,
+            project_id: project_id,
+            create_time: create_time,
+            wasm: Vec::new(),
+            sha256: None,
+        }
+    }
+
+    pub fn append_chunk(&mut self, chunk: &[u8], sender: Principal) -> Result<u64, ManageError> {
+        if self.uploader != sender {
+            return Err(ManageError::NotInitiator);
+        }
+        if self.sha256.is_some() {
+            return Err(ManageError::InvalidState(
+                "upload is already finalized".to_string(),
+            ));
+        }
+        if self.wasm.len() + chunk.len() > constant::WASM_MAX_SIZE {
+            return Err(ManageError::InvalidArgument(format!(
+                "wasm module exceeds the maximum size of {} bytes",
+                constant::WASM_MAX_SIZE
+            )));
+        }
+        self.wasm.extend_from_slice(chunk);
+        Ok(self.wasm.len() as u64)
+    }
+
+    pub fn finalize(&mut self, sha256: Vec<u8>, sender: Principal) -> Result<(), ManageError> {
+        if self.uploader != sender {
+            return Err(ManageError::NotInitiator);
+        }
+        if self.sha256.is_some() {
+            return Err(ManageError::InvalidState(
+                "upload is already finalized".to_string(),
+            ));
+        }
+        let hash = Sha256::digest(&self.wasm).to_vec();
+        if hash != sha256 {
+            return Err(ManageError::HashMismatch {
+                expected: sha256,
+                actual: Some(hash),
+            });
+        }
+        self.sha256 = Some(hash);
+        Ok(())
+    }
+
+    // The module of a finalized upload, if it belongs to the given project
+    pub fn module(
+        &self,
+        account: Principal,
+        group_id: u64,
+        project_id: u64,
+    ) -> Result<&[u8], ManageError> {
+        if !self.belongs_to(account, group_id, project_id) {
+            return Err(ManageError::not_found(ResourceKind::Upload, self.id));
+        }
+        if self.sha256.is_none() {
+            return Err(ManageError::InvalidState(
+                "upload is not finalized".to_string(),
+            ));
+        }
+        Ok(&self.wasm)
+    }
+
+    pub fn belongs_to(&self, account: Principal, group_id: u64, project_id: u64) -> bool {
+        self.account == account && self.group_id == group_id && self.project_id == project_id
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        let expiration = match self.sha256 {
+            None => constant::UPLOAD_EXPIRATION,
+            Some(_) => constant::FINALIZED_UPLOAD_EXPIRATION,
+        };
+        now > self.create_time + expiration
+    }
+
+    pub fn info(&self) -> UploadInfo {
+        UploadInfo {
+            id: self.id,
+            uploader: self.uploader,
+            create_time: self.create_time,
+            size: self.wasm.len() as u64,
+            sha256: self.sha256.clone(),
+        }
+    }
+}
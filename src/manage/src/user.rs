@@ -1,8 +1,12 @@
 use crate::authority::Authority;
+use crate::error::{ManageError, ResourceKind};
 use crate::group::Group;
-use crate::manage::{CanisterStatusResponse, InstallCodeMode};
+use crate::manage::{CanisterSettings, CanisterStatusResponse, Controllability, InstallCodeMode};
 use crate::member::Member;
+use crate::permission::Permission;
 use crate::project::Project;
+use crate::registry::InstalledModule;
+use crate::role::Role;
 use crate::types::Profile;
 use ic_cdk::api::caller;
 use ic_cdk::export::candid::Nat;
@@ -14,21 +18,6 @@ use std::pin::Pin;
 #[macro_use]
 use crate::operation;
 
-#[derive(CandidType, Debug, Deserialize, Clone, Eq, PartialEq, Copy)]
-pub struct RelationProject {
-    pub group_id: u64,
-    pub project_id: u64,
-}
-
-impl RelationProject {
-    pub fn new(group_id: u64, project_id: u64) -> Self {
-        Self {
-            group_id: group_id,
-            project_id: project_id,
-        }
-    }
-}
-
 #[derive(CandidType, Debug, Deserialize, Clone)]
 pub struct User {
     pub user_name: String,
@@ -40,8 +29,6 @@ pub struct User {
     // Users can manage multiple groups, and each group contains multiple items, which is convenient for unified management
     pub groups: HashMap<u64, Group>,
 
-    pub relation_project: HashMap<Principal, Vec<RelationProject>>,
-
     pub create_time: u64,
 }
 
@@ -52,22 +39,21 @@ impl User {
             profile: profile,
             identity: identity,
             groups: HashMap::new(),
-            relation_project: HashMap::new(),
             create_time: create_time,
         }
     }
 
-    fn identity_check(&self, sender: Principal) -> Result<(), String> {
+    fn identity_check(&self, sender: Principal) -> Result<(), ManageError> {
         if self.identity == sender {
             return Ok(());
         }
-        return Err("no permission".to_string());
+        return Err(ManageError::NotOwner);
     }
 
-    pub fn get_user_info(&self, sender: Principal) -> Result<User, String> {
+    pub fn get_user_info(&self, sender: Principal) -> Result<User, ManageError> {
         if sender != self.identity {
             if let Profile::Private = self.profile {
-                return Err("user information is private and cannot be viewed".to_string());
+                return Err(ManageError::permission_denied(Permission::ViewStatus, None));
             }
 
             let mut cp_user = self.clone();
@@ -79,12 +65,7 @@ impl User {
                         return true;
                     };
                     if let Some(mem) = v.members.get(&sender) {
-                        if let Some(expir) = mem.expiration_time {
-                            if expir < ic_cdk::api::time() {
-                                return false;
-                            }
-                        }
-                        return true;
+                        return mem.is_active(ic_cdk::api::time());
                     }
                     return false;
                 })
@@ -97,172 +78,209 @@ impl User {
         }
     }
 
-    pub fn add_group(&mut self, group: Group, sender: Principal) -> Result<(), String> {
+    pub fn add_group(&mut self, mut group: Group, sender: Principal) -> Result<(), ManageError> {
         self.identity_check(sender)?;
         if self.groups.contains_key(&group.id) {
-            return Err("group id already exists".to_string());
+            return Err(ManageError::already_exists(ResourceKind::Group, group.id));
         }
+        // Members and projects are only added through invitations and add_project
+        group.members.clear();
+        group.roles.clear();
+        group.projects.clear();
+        group.owner = Some(self.identity);
         self.groups.insert(group.id, group);
         Ok(())
     }
 
-    pub fn remove_group(&mut self, group_id: u64, sender: Principal) -> Result<(), String> {
+    pub fn remove_group(
+        &mut self,
+        group_id: u64,
+        sender: Principal,
+    ) -> Result<Option<Group>, ManageError> {
         self.identity_check(sender)?;
-        self.groups.remove(&group_id);
-        Ok(())
+        Ok(self.groups.remove(&group_id))
     }
 
-    pub fn add_group_relation(
+    pub fn update_group_member_authority(
         &mut self,
-        relation_project_user: Principal,
-        group_id: u64,
-    ) -> Result<(), String> {
-        let rela = RelationProject::new(group_id, 0);
-        if let Some(relation) = self.relation_project.get_mut(&relation_project_user) {
-            for data in relation.iter() {
-                if *data == rela {
-                    return Ok(());
-                }
+        group_id: u64,
+        member: Principal,
+        auth: Authority,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        self.group_identity_check(group_id, Permission::ManageMembers, sender)?;
+        self.group_grant_check(group_id, &auth.permissions(), sender)?;
+        match self.groups.get_mut(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => {
+                group.update_member_authority(member, auth)?;
+                Ok(())
             }
-            relation.push(rela);
-        } else {
-            self.relation_project
-                .insert(relation_project_user, Vec::new());
-
-            self.relation_project
-                .get_mut(&relation_project_user)
-                .unwrap()
-                .push(rela);
         }
-
-        Ok(())
     }
 
-    pub fn remove_group_relation(
+    pub fn update_project_member_authority(
         &mut self,
-        relation_project_user: Principal,
-        group_id: u64,
-    ) -> Result<(), String> {
-        match self.relation_project.get_mut(&relation_project_user) {
-            None => Ok(()),
-            Some(projects) => {
-                let mut index: Option<usize> = None;
-                for (k, v) in projects.iter().enumerate() {
-                    if v.group_id == group_id {
-                        index = Some(k);
-                        break;
-                    }
-                }
-                if let Some(idx) = index {
-                    projects.remove(idx);
-                }
+        group_id: u64,
+        project_id: u64,
+        member: Principal,
+        auth: Authority,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        match self.groups.get_mut(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => {
+                group.update_project_member_authority(project_id, member, auth, sender)?;
                 Ok(())
             }
         }
     }
 
-    pub fn add_project_relation(
+    pub fn update_group_member_expiration(
         &mut self,
-        relation_project_user: Principal,
         group_id: u64,
-        project_id: u64,
-    ) -> Result<(), String> {
-        let rela = RelationProject::new(group_id, project_id);
-        if let Some(relation) = self.relation_project.get_mut(&relation_project_user) {
-            for data in relation.iter() {
-                if *data == rela {
-                    return Ok(());
-                }
-            }
-            relation.push(rela);
-        } else {
-            self.relation_project
-                .insert(relation_project_user, Vec::new());
-
-            self.relation_project
-                .get_mut(&relation_project_user)
-                .unwrap()
-                .push(rela);
+        member: Principal,
+        expiration_time: Option<u64>,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        self.group_identity_check(group_id, Permission::ManageMembers, sender)?;
+        match self.groups.get_mut(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group.update_member_expiration(member, expiration_time),
         }
-
-        Ok(())
     }
 
-    pub fn remove_project_relation(
+    pub fn update_project_member_expiration(
         &mut self,
-        relation_project_user: Principal,
+        group_id: u64,
         project_id: u64,
-    ) -> Result<(), String> {
-        match self.relation_project.get_mut(&relation_project_user) {
-            None => Ok(()),
-            Some(projects) => {
-                let mut index: Option<usize> = None;
-                for (k, v) in projects.iter().enumerate() {
-                    if v.project_id == project_id {
-                        index = Some(k);
-                        break;
-                    }
-                }
-                if let Some(idx) = index {
-                    projects.remove(idx);
-                }
-                Ok(())
+        member: Principal,
+        expiration_time: Option<u64>,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        match self.groups.get_mut(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => {
+                group.update_project_member_expiration(project_id, member, expiration_time, sender)
             }
         }
     }
 
-    pub fn update_group_member_authority(
+    pub fn update_group_member_role(
         &mut self,
         group_id: u64,
         member: Principal,
-        auth: Authority,
+        role: Option<String>,
         sender: Principal,
-    ) -> Result<(), String> {
-        self.identity_check(sender)?;
+    ) -> Result<(), ManageError> {
+        self.group_identity_check(group_id, Permission::ManageMembers, sender)?;
         match self.groups.get_mut(&group_id) {
-            None => return Err("Group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => {
-                group.update_member_authority(member, auth)?;
-                Ok(())
+                group.role_grant_check(None, &role, sender)?;
+                group.update_member_role(member, role)
             }
         }
     }
 
-    pub fn update_project_member_authority(
+    pub fn update_canister_acl(
         &mut self,
         group_id: u64,
         project_id: u64,
-        member: Principal,
-        auth: Authority,
+        canister: Principal,
+        principal: Principal,
+        permissions: Option<Vec<Permission>>,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
         match self.groups.get_mut(&group_id) {
-            None => return Err("Group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => {
-                group.update_project_member_authority(project_id, member, auth, sender)?;
-                Ok(())
+                group.update_canister_acl(project_id, canister, principal, permissions, sender)
             }
         }
     }
 
-    pub fn add_project(
+    pub fn update_project_member_role(
         &mut self,
         group_id: u64,
-        project: Project,
+        project_id: u64,
+        member: Principal,
+        role: Option<String>,
         sender: Principal,
-    ) -> Result<Vec<Principal>, String> {
-        let mut members: Vec<Principal> = Vec::new();
-        let project_id: u64;
+    ) -> Result<(), ManageError> {
+        match self.groups.get_mut(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group.update_project_member_role(project_id, member, role, sender),
+        }
+    }
+
+    pub fn create_group_role(
+        &mut self,
+        group_id: u64,
+        role: Role,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        self.group_identity_check(group_id, Permission::ManageRoles, sender)?;
+        self.group_grant_check(group_id, &role.permissions, sender)?;
+        match self.groups.get_mut(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group.create_role(role),
+        }
+    }
+
+    pub fn update_group_role(
+        &mut self,
+        group_id: u64,
+        role: Role,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        self.group_identity_check(group_id, Permission::ManageRoles, sender)?;
+        self.group_grant_check(group_id, &role.permissions, sender)?;
+        match self.groups.get_mut(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group.update_role(role),
+        }
+    }
 
+    pub fn delete_group_role(
+        &mut self,
+        group_id: u64,
+        name: &str,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        self.group_identity_check(group_id, Permission::ManageRoles, sender)?;
         match self.groups.get_mut(&group_id) {
-            None => return Err("Group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group.delete_role(name),
+        }
+    }
+
+    pub fn get_group_roles(
+        &self,
+        group_id: u64,
+        sender: Principal,
+    ) -> Result<Vec<Role>, ManageError> {
+        match self.groups.get(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => {
-                members = project.members.keys().map(|x| *x).collect();
-                project_id = project.id;
-                group.add_project(project.clone(), sender)?;
+                if Profile::Private == group.visibility {
+                    self.group_identity_check(group_id, Permission::ViewStatus, sender)?;
+                }
+                Ok(group.roles.values().cloned().collect())
             }
-        };
-        Ok(members)
+        }
+    }
+
+    pub fn add_project(
+        &mut self,
+        group_id: u64,
+        project: Project,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        match self.groups.get_mut(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group.add_project(project, sender),
+        }
     }
 
     pub fn remove_project(
@@ -270,51 +288,72 @@ impl User {
         group_id: u64,
         project_id: u64,
         sender: Principal,
-    ) -> Result<Vec<Principal>, String> {
+    ) -> Result<Vec<Principal>, ManageError> {
         let mut members: Vec<Principal> = Vec::new();
 
         match self.groups.get_mut(&group_id) {
-            None => return Err("Group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => {
                 if let Some(project) = group.projects.get(&project_id) {
                     members = project.members.keys().map(|x| *x).collect();
                 }
-                if self.identity == sender {
-                    group.projects.remove(&project_id);
-                } else {
-                    group.remove_project(project_id, sender)?;
-                }
+                group.remove_project(project_id, sender)?;
             }
         };
 
         Ok(members)
     }
 
+    // Checks of add_group_member and add_project_member, done when the invitation is issued
+    pub fn invite_check(
+        &self,
+        group_id: u64,
+        project_id: Option<u64>,
+        member: &Member,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        let group = match self.groups.get(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group,
+        };
+        let members = match project_id {
+            None => {
+                self.group_identity_check(group_id, Permission::ManageMembers, sender)?;
+                &group.members
+            }
+            Some(project_id) => {
+                self.project_identity_check(
+                    group_id,
+                    project_id,
+                    Permission::ManageMembers,
+                    sender,
+                )?;
+                &self.get_project(group_id, project_id)?.members
+            }
+        };
+        if members.contains_key(&member.identity) {
+            return Err(ManageError::already_exists(
+                ResourceKind::Member,
+                member.identity,
+            ));
+        }
+        group.role_check(&member.role)?;
+        group.member_grant_check(project_id, member, sender)?;
+        Ok(())
+    }
+
     pub fn add_group_member(
         &mut self,
         group_id: u64,
         member: Member,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
+        self.group_identity_check(group_id, Permission::ManageMembers, sender)?;
         match self.groups.get_mut(&group_id) {
-            None => return Err("Group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => {
-                if sender == self.identity {
-                    group.add_member(member)?;
-                    Ok(())
-                } else {
-                    let mut approve = false;
-                    if let Some(account) = group.members.get(&sender) {
-                        if let Authority::Operational = account.authority {
-                            approve = true;
-                        }
-                    }
-                    if approve {
-                        group.add_member(member)?;
-                        return Ok(());
-                    }
-                    return Err("Insufficient permissions".to_string());
-                }
+                group.member_grant_check(None, &member, sender)?;
+                group.add_member(member)
             }
         }
     }
@@ -324,16 +363,13 @@ impl User {
         group_id: u64,
         member: Principal,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
         if member != sender {
-            self.identity_check(sender)?;
+            self.group_identity_check(group_id, Permission::ManageMembers, sender)?;
         }
         match self.groups.get_mut(&group_id) {
-            None => return Err("Group does not exist".to_string()),
-            Some(group) => {
-                group.remove_member(member);
-                Ok(())
-            }
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group.remove_member(member),
         }
     }
 
@@ -343,9 +379,9 @@ impl User {
         project_id: u64,
         member: Member,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
         match self.groups.get_mut(&group_id) {
-            None => return Err("group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => group.add_project_member(project_id, member, sender),
         }
     }
@@ -356,9 +392,9 @@ impl User {
         project_id: u64,
         member: Principal,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
         match self.groups.get_mut(&group_id) {
-            None => return Err("group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => group.remove_project_member(project_id, member, sender),
         }
     }
@@ -369,9 +405,9 @@ impl User {
         project_id: u64,
         canister: Principal,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
         match self.groups.get_mut(&group_id) {
-            None => return Err("group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => group.add_project_canister(project_id, canister, sender),
         }
     }
@@ -382,22 +418,133 @@ impl User {
         project_id: u64,
         canister: Principal,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
         match self.groups.get_mut(&group_id) {
-            None => return Err("group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => group.remove_project_canister(project_id, canister, sender),
         }
     }
 
+    pub fn group_identity_check(
+        &self,
+        group_id: u64,
+        opt: Permission,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        match self.groups.get(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group.identity_check(opt, sender),
+        }
+    }
+
+    pub fn group_grant_check(
+        &self,
+        group_id: u64,
+        permissions: &[Permission],
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        match self.groups.get(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group.grant_check(None, permissions, sender),
+        }
+    }
+
+    pub fn get_project(&self, group_id: u64, project_id: u64) -> Result<&Project, ManageError> {
+        match self.groups.get(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group
+                .projects
+                .get(&project_id)
+                .ok_or(ManageError::not_found(ResourceKind::Project, project_id)),
+        }
+    }
+
+    pub fn project_identity_check(
+        &self,
+        group_id: u64,
+        project_id: u64,
+        opt: Permission,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        match self.groups.get(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group.project_identity_check(project_id, opt, sender),
+        }
+    }
+
+    pub fn canister_identity_check(
+        &self,
+        group_id: u64,
+        project_id: u64,
+        canister: Principal,
+        opt: Permission,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        match self.groups.get(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group.canister_identity_check(project_id, canister, opt, sender),
+        }
+    }
+
+    pub fn register_project_canister(
+        &mut self,
+        group_id: u64,
+        project_id: u64,
+        canister: Principal,
+    ) -> Result<(), ManageError> {
+        match self.groups.get_mut(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group.register_canister(project_id, canister),
+        }
+    }
+
+    pub fn unregister_project_canister(
+        &mut self,
+        group_id: u64,
+        project_id: u64,
+        canister: Principal,
+    ) -> Result<(), ManageError> {
+        match self.groups.get_mut(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group.unregister_canister(project_id, canister),
+        }
+    }
+
+    pub fn set_installed_module(
+        &mut self,
+        group_id: u64,
+        project_id: u64,
+        canister: Principal,
+        module: Option<InstalledModule>,
+    ) -> Result<(), ManageError> {
+        match self.groups.get_mut(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group.set_installed_module(project_id, canister, module),
+        }
+    }
+
+    pub fn set_controllability(
+        &mut self,
+        group_id: u64,
+        project_id: u64,
+        canister: Principal,
+        controllability: Controllability,
+    ) -> Result<(), ManageError> {
+        match self.groups.get_mut(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group.set_controllability(project_id, canister, controllability),
+        }
+    }
+
     pub fn update_project_git_repo_url(
         &mut self,
         group_id: u64,
         project_id: u64,
         git: &str,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
         match self.groups.get_mut(&group_id) {
-            None => return Err("group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => group.update_git_repo_url(project_id, git, sender),
         }
     }
@@ -408,9 +555,9 @@ impl User {
         project_id: u64,
         floor: Nat,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
         match self.groups.get_mut(&group_id) {
-            None => return Err("group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => group.update_canister_cycle_floor(project_id, floor, sender),
         }
     }
@@ -421,9 +568,9 @@ impl User {
         project_id: u64,
         visibility: Profile,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
         match self.groups.get_mut(&group_id) {
-            None => return Err("group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => group.update_visibility(project_id, visibility, sender),
         }
     }
@@ -434,9 +581,9 @@ impl User {
         project_id: u64,
         description: &str,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
         match self.groups.get_mut(&group_id) {
-            None => return Err("group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => group.update_description(project_id, description, sender),
         }
     }
@@ -447,22 +594,52 @@ impl User {
         project_id: u64,
         canister: Principal,
         sender: Principal,
-    ) -> Result<impl Future<Output = Result<(CanisterStatusResponse, Nat), String>>, String> {
+    ) -> Result<impl Future<Output = Result<(CanisterStatusResponse, Nat), ManageError>>, ManageError>
+    {
         match self.groups.get(&group_id) {
-            None => return Err("group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => group.get_canister_status(project_id, canister, sender),
         }
     }
 
+    pub fn canister_read_check(
+        &self,
+        group_id: u64,
+        project_id: u64,
+        canister: Principal,
+        sender: Principal,
+    ) -> Result<(), ManageError> {
+        match self.groups.get(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => group.canister_read_check(project_id, canister, sender),
+        }
+    }
+
+    pub fn update_project_canister_settings(
+        &self,
+        group_id: u64,
+        project_id: u64,
+        canister: Principal,
+        settings: CanisterSettings,
+        sender: Principal,
+    ) -> Result<impl Future<Output = Result<(), ManageError>>, ManageError> {
+        match self.groups.get(&group_id) {
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => {
+                group.update_project_canister_settings(project_id, canister, settings, sender)
+            }
+        }
+    }
+
     pub fn stop_project_canister(
         &self,
         group_id: u64,
         project_id: u64,
         canister: Principal,
         sender: Principal,
-    ) -> Result<impl Future<Output = Result<(), String>>, String> {
+    ) -> Result<impl Future<Output = Result<(), ManageError>>, ManageError> {
         match self.groups.get(&group_id) {
-            None => return Err("group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => group.stop_project_canister(project_id, canister, sender),
         }
     }
@@ -473,9 +650,9 @@ impl User {
         project_id: u64,
         canister: Principal,
         sender: Principal,
-    ) -> Result<impl Future<Output = Result<(), String>>, String> {
+    ) -> Result<impl Future<Output = Result<(), ManageError>>, ManageError> {
         match self.groups.get(&group_id) {
-            None => return Err("group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => group.start_project_canister(project_id, canister, sender),
         }
     }
@@ -486,9 +663,9 @@ impl User {
         project_id: u64,
         canister: Principal,
         sender: Principal,
-    ) -> Result<impl Future<Output = Result<(), String>>, String> {
+    ) -> Result<impl Future<Output = Result<(), ManageError>>, ManageError> {
         match self.groups.get(&group_id) {
-            None => return Err("group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => group.delete_project_canister(project_id, canister, sender),
         }
     }
@@ -502,9 +679,9 @@ impl User {
         wasm: Vec<u8>,
         args: Vec<u8>,
         sender: Principal,
-    ) -> Result<impl Future<Output = Result<(), String>>, String> {
+    ) -> Result<impl Future<Output = Result<(), ManageError>>, ManageError> {
         match self.groups.get(&group_id) {
-            None => return Err("group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => {
                 group.install_code(project_id, canister, install_mod, wasm, args, sender)
             }
@@ -516,7 +693,7 @@ impl User {
         group_id: u64,
         project_id: u64,
         sender: Principal,
-    ) -> Result<Option<Project>, String> {
+    ) -> Result<Option<Project>, ManageError> {
         match self.groups.get(&group_id) {
             None => return Ok(None),
             Some(group) => {
@@ -535,7 +712,7 @@ impl User {
         &self,
         group_id: u64,
         sender: Principal,
-    ) -> Result<Option<Group>, String> {
+    ) -> Result<Option<Group>, ManageError> {
         match self.groups.get(&group_id) {
             None => return Ok(None),
             Some(group) => match group.visibility {
@@ -543,14 +720,14 @@ impl User {
                     return Ok(Some(group.clone()));
                 }
                 Profile::Private => match group.members.get(&sender) {
-                    None => {
+                    Some(member) if member.is_active(ic_cdk::api::time()) => {
+                        return Ok(Some(group.clone()));
+                    }
+                    _ => {
                         if self.identity == sender {
                             return Ok(Some(group.clone()));
                         }
-                        return Err("No permission".to_string());
-                    }
-                    Some(_) => {
-                        return Ok(Some(group.clone()));
+                        return Err(ManageError::permission_denied(Permission::ViewStatus, None));
                     }
                 },
             },
@@ -561,16 +738,18 @@ impl User {
         &self,
         group_id: u64,
         member: Principal,
-    ) -> Result<Member, String> {
+    ) -> Result<Member, ManageError> {
         match self.groups.get(&group_id) {
-            None => return Err("group does not exist".to_string()),
-            Some(group) => {
-                let res = group
-                    .members
-                    .get(&member)
-                    .ok_or("Member not found".to_string());
-                Ok(res.unwrap().clone())
-            }
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
+            Some(group) => match group.members.get(&member) {
+                None => Err(ManageError::not_found(ResourceKind::Member, member)),
+                Some(data) => {
+                    if !data.is_active(ic_cdk::api::time()) {
+                        return Err(ManageError::MemberExpired);
+                    }
+                    Ok(data.clone())
+                }
+            },
         }
     }
 
@@ -582,9 +761,9 @@ impl User {
         visibility: Profile,
         url: String,
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
         match self.groups.get_mut(&group_id) {
-            None => return Err("group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => {
                 group.update_basic_information(name, description, visibility, url, sender)
             }
@@ -599,20 +778,16 @@ impl User {
         description: String,
         visibility: Profile,
         git: String,
-        canister_cycle_floor: Nat,
-        canisters: &[Principal],
         sender: Principal,
-    ) -> Result<(), String> {
+    ) -> Result<(), ManageError> {
         match self.groups.get_mut(&group_id) {
-            None => return Err("group does not exist".to_string()),
+            None => return Err(ManageError::not_found(ResourceKind::Group, group_id)),
             Some(group) => group.update_project_basic_information(
                 project_id,
                 name,
                 description,
                 visibility,
                 git,
-                canister_cycle_floor,
-                canisters,
                 sender,
             ),
         }
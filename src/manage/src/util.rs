@@ -1,8 +1,63 @@
-use ic_cdk::export::candid::Principal;
+use crate::error::{ManageError, ResourceKind};
+use ic_cdk::export::candid::{Nat, Principal};
 
 pub fn is_controller(content: String, controller: Principal) -> bool {
-    let content: Vec<&str> = content.split("\n").collect();
-    let controller = controller.to_string();
-    let controller_content: Vec<&str> = content[1].split(" ").collect();
-    controller_content.contains(&controller.as_str())
+    parse_controllers(&content).contains(&controller)
+}
+
+// Controllers listed in the reject message of a call made by a non-controller
+pub fn parse_controllers(content: &str) -> Vec<Principal> {
+    match content.split("\n").nth(1) {
+        None => Vec::new(),
+        Some(line) => line
+            .split(" ")
+            .filter_map(|text| Principal::from_text(text).ok())
+            .collect(),
+    }
+}
+
+// Cycles amounts are carried as Nat in the Candid interface but paid as u128
+pub fn nat_to_u128(value: &Nat) -> u128 {
+    u128::try_from(&value.0).unwrap_or(u128::MAX)
+}
+
+// Principals of users end with this byte, canister principals do not
+const SELF_AUTHENTICATING_TAG: u8 = 0x02;
+
+pub fn is_self_authenticating(principal: &Principal) -> bool {
+    principal.as_slice().last() == Some(&SELF_AUTHENTICATING_TAG)
+}
+
+// Add or remove one controller, keeping the manage canister and at least one user as controllers
+pub fn change_controllers(
+    mut controllers: Vec<Principal>,
+    controller: Principal,
+    add: bool,
+    manage_canister: Principal,
+) -> Result<Vec<Principal>, ManageError> {
+    if add {
+        if controllers.contains(&controller) {
+            return Err(ManageError::already_exists(
+                ResourceKind::Controller,
+                controller,
+            ));
+        }
+        controllers.push(controller);
+        return Ok(controllers);
+    }
+    if controller == manage_canister {
+        return Err(ManageError::InvalidArgument(
+            "the manage canister cannot be removed from the controllers".to_string(),
+        ));
+    }
+    if !controllers.contains(&controller) {
+        return Err(ManageError::not_found(ResourceKind::Controller, controller));
+    }
+    controllers.retain(|c| *c != controller);
+    if !controllers.iter().any(is_self_authenticating) {
+        return Err(ManageError::InvalidArgument(
+            "the canister must keep at least one user controller".to_string(),
+        ));
+    }
+    Ok(controllers)
 }